@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use symbol_waterfall::{Waterfall, WaterfallConfig};
+
+fn waterfall(width: usize, height: usize, coarse_factor: usize) -> Waterfall {
+    let config = WaterfallConfig {
+        seed: Some(0),
+        coarse_factor,
+        ..Default::default()
+    };
+    let mut waterfall = Waterfall::headless(config, width, height).unwrap();
+    waterfall.warm_up().unwrap();
+    waterfall
+}
+
+fn step(c: &mut Criterion) {
+    for coarse_factor in [1, 2] {
+        let mut waterfall = waterfall(400, 120, coarse_factor);
+        c.bench_function(&format!("step 400x120 coarse {}x", coarse_factor), |b| {
+            b.iter(|| waterfall.step().unwrap())
+        });
+    }
+}
+
+fn render(c: &mut Criterion) {
+    for coarse_factor in [1, 2] {
+        let mut waterfall = waterfall(400, 120, coarse_factor);
+        c.bench_function(&format!("render 400x120 coarse {}x", coarse_factor), |b| {
+            b.iter(|| waterfall.render().unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, step, render);
+criterion_main!(benches);
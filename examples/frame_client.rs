@@ -0,0 +1,41 @@
+//! Prints frames sent by a `FrameStreamWriter`, like the ones from the
+//! `frame_server` example.
+//!
+//! cargo run --example frame_client -- 127.0.0.1:7878
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    net::TcpStream,
+};
+
+use anyhow::{Context, Result};
+
+fn main() -> Result<()> {
+    let address = env::args().nth(1).context("usage: frame_client ADDRESS")?;
+    let mut stream = TcpStream::connect(&address)?;
+    let mut stdout = io::stdout();
+
+    loop {
+        let mut length = [0; 4];
+        if stream.read_exact(&mut length).is_err() {
+            return Ok(());
+        }
+        let mut frame = vec![0; u32::from_be_bytes(length) as usize];
+        stream.read_exact(&mut frame)?;
+
+        // home the cursor and clear what's left of every line and the
+        // screen below, lines are sent without trailing blanks so the last
+        // frame would show through otherwise
+        stdout.write_all(b"\x1b[H")?;
+        for (i, line) in frame.split(|&byte| byte == b'\n').enumerate() {
+            if i > 0 {
+                stdout.write_all(b"\n")?;
+            }
+            stdout.write_all(line)?;
+            stdout.write_all(b"\x1b[K")?;
+        }
+        stdout.write_all(b"\x1b[J")?;
+        stdout.flush()?;
+    }
+}
@@ -0,0 +1,26 @@
+//! Streams an 80x24 waterfall to the first `frame_client` that connects.
+//!
+//! cargo run --example frame_server -- 127.0.0.1:7878
+
+use std::{env, net::TcpListener};
+
+use anyhow::{Context, Result};
+use symbol_waterfall::{FrameStreamWriter, Waterfall, WaterfallConfig};
+
+const WIDTH: usize = 80;
+const HEIGHT: usize = 24;
+
+fn main() -> Result<()> {
+    let address = env::args().nth(1).context("usage: frame_server ADDRESS")?;
+    let (stream, _) = TcpListener::bind(&address)?.accept()?;
+
+    let mut waterfall = Waterfall::with_renderer_and_size(
+        WaterfallConfig::film(),
+        FrameStreamWriter::new(stream),
+        WIDTH,
+        HEIGHT,
+    )?;
+    // stops with an error once the client hangs up
+    waterfall.run()?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time source of `Waterfall::run_with_clock`.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Wall clock time, sleeping blocks the thread.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Virtual time that only moves when slept on, for testing frame pacing
+/// without waiting.
+pub struct ManualClock {
+    now: Instant,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            now: Instant::now(),
+        }
+    }
+
+    /// Moves time forward without a sleep.
+    pub fn advance(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
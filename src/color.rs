@@ -0,0 +1,129 @@
+// hue in degrees 0.0 to 360.0, saturation and value 0.0 to 1.0
+pub(crate) fn rgb_to_hsv(color: (u8, u8, u8)) -> (f32, f32, f32) {
+    let (r, g, b) = (
+        color.0 as f32 / 255.0,
+        color.1 as f32 / 255.0,
+        color.2 as f32 / 255.0,
+    );
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+pub(crate) fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> (u8, u8, u8) {
+    let chroma = value * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+
+    (
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8,
+    )
+}
+
+// nearest entry of the xterm 256 color palette, skipping the 16 system colors
+pub(crate) fn rgb_to_ansi256(color: (u8, u8, u8)) -> u8 {
+    let (r, g, b) = color;
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            249..=255 => 231,
+            v => 232 + ((v as u16 - 8) * 24 / 241) as u8,
+        };
+    }
+
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    (16 + 36 * level(r) + 6 * level(g) + level(b)) as u8
+}
+
+// usual rgb values of the 16 system colors, in ansi order
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// index into the 16 system colors closest to the given color
+pub(crate) fn rgb_to_ansi16(color: (u8, u8, u8)) -> usize {
+    let distance = |c: &(u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(c.0, color.0) + d(c.1, color.1) + d(c.2, color.2)
+    };
+
+    ANSI16
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, c)| distance(c))
+        .map_or(0, |(i, _)| i)
+}
+
+// relative luminance with the Rec. 709 weights, 0.0 to 1.0
+pub(crate) fn luminance(color: (u8, u8, u8)) -> f32 {
+    (0.2126 * color.0 as f32 + 0.7152 * color.1 as f32 + 0.0722 * color.2 as f32) / 255.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greys_map_onto_the_grey_ramp() {
+        assert_eq!(rgb_to_ansi256((0, 0, 0)), 16);
+        assert_eq!(rgb_to_ansi256((8, 8, 8)), 232);
+        assert_eq!(rgb_to_ansi256((248, 248, 248)), 255);
+        assert_eq!(rgb_to_ansi256((255, 255, 255)), 231);
+    }
+
+    #[test]
+    fn primaries_map_onto_the_color_cube() {
+        assert_eq!(rgb_to_ansi256((255, 0, 0)), 196);
+        assert_eq!(rgb_to_ansi256((0, 255, 0)), 46);
+        assert_eq!(rgb_to_ansi256((0, 0, 255)), 21);
+        assert_eq!(rgb_to_ansi256((255, 255, 0)), 226);
+    }
+
+    #[test]
+    fn ansi16_picks_the_nearest_system_color() {
+        assert_eq!(rgb_to_ansi16((0, 0, 0)), 0);
+        assert_eq!(rgb_to_ansi16((200, 10, 10)), 9);
+        assert_eq!(rgb_to_ansi16((0, 100, 0)), 2);
+        assert_eq!(rgb_to_ansi16((100, 100, 100)), 8);
+        assert_eq!(rgb_to_ansi16((190, 190, 190)), 7);
+        assert_eq!(rgb_to_ansi16((250, 250, 250)), 15);
+    }
+}
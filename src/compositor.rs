@@ -0,0 +1,253 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{color, Renderer, Result, Waterfall, WaterfallConfig, WaterfallError};
+
+/// How the lit cells of a `Compositor` layer combine with the layers below.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// lit cells cover whatever is below
+    #[default]
+    Over,
+    /// colors are summed, overlapping trails glow brighter
+    Add,
+    /// the brighter of the two cells wins
+    Lighten,
+}
+
+// glyph, color and background of a cell as a layer drew it
+#[derive(Clone, Copy)]
+struct Cell {
+    glyph: char,
+    color: (u8, u8, u8),
+    background: Option<(u8, u8, u8)>,
+}
+
+const BLANK: Cell = Cell {
+    glyph: ' ',
+    color: (0, 0, 0),
+    background: None,
+};
+
+impl Cell {
+    fn is_lit(&self) -> bool {
+        self.background.is_some() || (self.glyph != ' ' && self.color != (0, 0, 0))
+    }
+
+    fn luminance(&self) -> f32 {
+        color::luminance(self.background.unwrap_or(self.color))
+    }
+}
+
+// renderer of a single layer, keeps the last frame for the compositor
+struct LayerBuffer {
+    cells: Arc<Mutex<Vec<Cell>>>,
+    width: usize,
+}
+
+impl LayerBuffer {
+    fn store(&mut self, x: usize, y: usize, cell: Cell) {
+        if x >= self.width {
+            return;
+        }
+        if let Some(slot) = self.cells.lock().unwrap().get_mut(y * self.width + x) {
+            *slot = cell;
+        }
+    }
+}
+
+impl Renderer for LayerBuffer {
+    fn draw_cell(&mut self, x: usize, y: usize, glyph: char, color: (u8, u8, u8)) -> Result<()> {
+        self.store(
+            x,
+            y,
+            Cell {
+                glyph,
+                color,
+                background: None,
+            },
+        );
+        Ok(())
+    }
+
+    fn draw_cell_on(
+        &mut self,
+        x: usize,
+        y: usize,
+        glyph: char,
+        color: (u8, u8, u8),
+        background: (u8, u8, u8),
+    ) -> Result<()> {
+        let background = Some(background).filter(|&background| background != (0, 0, 0));
+        self.store(
+            x,
+            y,
+            Cell {
+                glyph,
+                color,
+                background,
+            },
+        );
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct Layer {
+    waterfall: Waterfall,
+    cells: Arc<Mutex<Vec<Cell>>>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    blend: BlendMode,
+}
+
+/// Runs several waterfalls side by side or on top of each other and draws
+/// them as one picture. Every layer covers its own region of the output and
+/// is blended onto the layers added before it.
+pub struct Compositor {
+    renderer: Box<dyn Renderer + Send>,
+    width: usize,
+    height: usize,
+    layers: Vec<Layer>,
+}
+
+impl Compositor {
+    /// Compositor drawing a `width` by `height` picture into `renderer`.
+    pub fn new(renderer: impl Renderer + Send + 'static, width: usize, height: usize) -> Self {
+        Compositor {
+            renderer: Box::new(renderer),
+            width,
+            height,
+            layers: vec![],
+        }
+    }
+
+    /// Adds a waterfall covering `width` by `height` cells from `x`, `y` on
+    /// top of the existing layers, anything past the output is cut off.
+    /// Returns the new layer for further setup.
+    pub fn add_layer(
+        &mut self,
+        config: WaterfallConfig,
+        (x, y): (usize, usize),
+        (width, height): (usize, usize),
+        blend: BlendMode,
+    ) -> Result<&mut Waterfall> {
+        if x >= self.width || y >= self.height {
+            return Err(WaterfallError::OutOfBounds {
+                x,
+                y: Some(y),
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        let cells = Arc::new(Mutex::new(vec![BLANK; width * height]));
+        let buffer = LayerBuffer {
+            cells: cells.clone(),
+            width,
+        };
+        let waterfall = Waterfall::with_size(config, Box::new(buffer), width, height)?;
+        self.layers.push(Layer {
+            waterfall,
+            cells,
+            x,
+            y,
+            width,
+            height,
+            blend,
+        });
+        Ok(&mut self.layers.last_mut().unwrap().waterfall)
+    }
+
+    /// Waterfall of the `index`th added layer.
+    pub fn layer_mut(&mut self, index: usize) -> Option<&mut Waterfall> {
+        self.layers.get_mut(index).map(|layer| &mut layer.waterfall)
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Advances every layer by one step.
+    pub fn step(&mut self) -> Result<()> {
+        for layer in self.layers.iter_mut() {
+            layer.waterfall.step()?;
+        }
+        Ok(())
+    }
+
+    /// Renders every layer and draws the blended picture.
+    pub fn render(&mut self) -> Result<()> {
+        let mut picture = vec![BLANK; self.width * self.height];
+        for layer in self.layers.iter_mut() {
+            layer.waterfall.render()?;
+            let cells = layer.cells.lock().unwrap();
+            let width = layer.width.min(self.width - layer.x);
+            let height = layer.height.min(self.height - layer.y);
+            for y in 0..height {
+                for x in 0..width {
+                    let cell = cells[y * layer.width + x];
+                    if !cell.is_lit() {
+                        continue;
+                    }
+                    let below = &mut picture[(layer.y + y) * self.width + layer.x + x];
+                    *below = blend(*below, cell, layer.blend);
+                }
+            }
+        }
+
+        self.renderer.begin_frame()?;
+        for (i, cell) in picture.iter().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            match cell.background {
+                Some(background) => self
+                    .renderer
+                    .draw_cell_on(x, y, cell.glyph, cell.color, background)?,
+                None => self.renderer.draw_cell(x, y, cell.glyph, cell.color)?,
+            }
+        }
+        self.renderer.flush()
+    }
+
+    /// Gives the target back in a usable state, see `Renderer::cleanup`.
+    pub fn cleanup(&mut self) -> Result<()> {
+        self.renderer.cleanup()
+    }
+}
+
+// `top` drawn onto `below` with `mode`, `top` is lit
+fn blend(below: Cell, top: Cell, mode: BlendMode) -> Cell {
+    if !below.is_lit() {
+        return top;
+    }
+    match mode {
+        BlendMode::Over => top,
+        BlendMode::Add => {
+            let add = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+                (
+                    a.0.saturating_add(b.0),
+                    a.1.saturating_add(b.1),
+                    a.2.saturating_add(b.2),
+                )
+            };
+            Cell {
+                glyph: if top.glyph != ' ' {
+                    top.glyph
+                } else {
+                    below.glyph
+                },
+                color: add(below.color, top.color),
+                background: match (below.background, top.background) {
+                    (Some(a), Some(b)) => Some(add(a, b)),
+                    (a, b) => a.or(b),
+                },
+            }
+        }
+        BlendMode::Lighten if top.luminance() >= below.luminance() => top,
+        BlendMode::Lighten => below,
+    }
+}
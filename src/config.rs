@@ -0,0 +1,496 @@
+use std::time::Duration;
+
+use crate::{
+    BlankStrategy, ColorDepth, InitialClear, NonTerminalOutput, ASCII_FALLBACK, FILM_SYMBOLS, FPS,
+    GENERATOR_DENSITY, MIN_TERMINAL_SIZE, REDUCED_MOTION_DENSITY, REDUCED_MOTION_SPEED,
+    RUNE_COLOR_BASE, RUNE_GENERATOR_COLOR, SYMBOLS,
+};
+
+/// How grid cells map onto terminal cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RenderMode {
+    /// one grid cell per terminal cell, drawn as its glyph
+    #[default]
+    Glyph,
+    /// Two grid rows per terminal row, drawn as `▀` with the upper cell as
+    /// foreground and the lower cell as background color. Doubles vertical
+    /// resolution at the cost of glyphs, and needs a font covering U+2580
+    /// and a terminal that can color backgrounds.
+    SubCell,
+    /// one grid cell per terminal cell, lit cells drawn as a solid block of
+    /// their color for a pixel grid look, needs a terminal that can color
+    /// backgrounds
+    Block,
+}
+
+impl RenderMode {
+    // grid rows needed to fill `height` terminal rows
+    pub(crate) fn grid_rows(self, height: usize) -> usize {
+        match self {
+            RenderMode::Glyph | RenderMode::Block => height,
+            RenderMode::SubCell => height * 2,
+        }
+    }
+}
+
+/// Named trail and head color pairs, for settings panels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorMode {
+    /// cyan trails with red heads
+    #[default]
+    Classic,
+    /// green trails with white heads
+    Matrix,
+    /// amber trails with pale yellow heads, like an old monochrome screen
+    Amber,
+    /// any colors without a name
+    Custom {
+        trail: (u8, u8, u8),
+        head: (u8, u8, u8),
+    },
+}
+
+impl ColorMode {
+    const NAMED: [ColorMode; 3] = [ColorMode::Classic, ColorMode::Matrix, ColorMode::Amber];
+
+    /// Trail and head color of the mode.
+    pub fn colors(self) -> ((u8, u8, u8), (u8, u8, u8)) {
+        match self {
+            ColorMode::Classic => (RUNE_COLOR_BASE, RUNE_GENERATOR_COLOR),
+            ColorMode::Matrix => ((0, 255, 70), (230, 255, 230)),
+            ColorMode::Amber => ((255, 176, 0), (255, 235, 170)),
+            ColorMode::Custom { trail, head } => (trail, head),
+        }
+    }
+
+    // named mode with exactly these colors, `Custom` otherwise
+    pub(crate) fn from_colors(trail: (u8, u8, u8), head: (u8, u8, u8)) -> Self {
+        Self::NAMED
+            .into_iter()
+            .find(|mode| mode.colors() == (trail, head))
+            .unwrap_or(ColorMode::Custom { trail, head })
+    }
+}
+
+/// Where the rain starts when the animation begins, it spreads to every
+/// column over the first second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpawnOrigin {
+    /// every column spawns right away
+    #[default]
+    All,
+    /// spreads from the middle column out to both edges
+    Center,
+    /// sweeps from the left edge to the right one
+    Left,
+    /// spreads from a random column out to both edges
+    Random,
+}
+
+/// Which way the rain runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    #[default]
+    Down,
+    /// trails start at the bottom and rise
+    Up,
+}
+
+impl Direction {
+    pub(crate) fn reversed(self) -> Self {
+        match self {
+            Direction::Down => Direction::Up,
+            Direction::Up => Direction::Down,
+        }
+    }
+}
+
+/// Way a single stream runs relative to `direction`, picked for every new
+/// generator from `direction_weights`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StreamDirection {
+    /// along `direction`
+    #[default]
+    Straight,
+    /// against `direction`, starting from the opposite edge
+    Reversed,
+    /// along `direction` while moving a column left per row
+    DiagonalLeft,
+    /// along `direction` while moving a column right per row
+    DiagonalRight,
+    /// across the screen to the left on a random row
+    SidewaysLeft,
+    /// across the screen to the right on a random row
+    SidewaysRight,
+}
+
+/// How fall speeds of new generators are spread over `speed`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpeedDist {
+    #[default]
+    Uniform,
+    /// most streams fall near the slow end, fast ones are rare
+    Slow,
+}
+
+/// What to do with characters of the alphabet that take up no cell, like
+/// combining marks, which would corrupt the grid if drawn on their own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZeroWidthSymbols {
+    /// fail with an error naming the character
+    #[default]
+    Reject,
+    /// silently drop them
+    Filter,
+}
+
+/// Tunables of a `Waterfall`. Defaults reproduce the original hardcoded look.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WaterfallConfig {
+    /// alphabet runes are picked from
+    pub symbols: String,
+    pub zero_width_symbols: ZeroWidthSymbols,
+    /// probability of 0.0 to 1.0 that a generator spawns in a column per step
+    pub density: f64,
+    /// Shifts spawning toward the center (positive) or the edges (negative)
+    /// of the screen. Clamped to -1.0..=1.0. Each column's probability is
+    /// `density * (1.0 + spawn_bias * c)` where `c` goes linearly from 1.0
+    /// in the center column to -1.0 at either edge, so 0.0 spawns uniformly
+    /// and 1.0 doubles the center while the edges never spawn.
+    pub spawn_bias: f32,
+    /// Scale `density` by `80 / width` so the total number of streams stays
+    /// the same on any terminal width, `density` then describes an 80
+    /// column terminal.
+    pub normalize_density_by_width: bool,
+    /// smallest (columns, rows) terminal the constructor accepts
+    pub min_size: (u16, u16),
+    /// Restore the cursor and colors of stdout when a panic happens. Off by
+    /// default since it replaces the process wide panic hook.
+    pub restore_on_panic: bool,
+    /// seeds every random decision of the simulation, `None` picks a random seed
+    pub seed: Option<u64>,
+    /// new generator heads flicker dim for a step or two before burning at
+    /// full brightness
+    pub ignition_flicker: bool,
+    /// glyph always drawn at the head of a trail instead of a random one
+    pub head_glyph: Option<char>,
+    /// re-randomize the glyph under every head each step, ignored when
+    /// `head_glyph` is set
+    pub animate_heads: bool,
+    /// frames per second of `Waterfall::run`
+    pub fps: u32,
+    /// how often `Waterfall::run` clears the whole grid, for displays left
+    /// running for days
+    pub refresh_interval: Option<Duration>,
+    /// `Waterfall::run` steps every frame but only renders every nth one.
+    /// Cuts terminal output on slow connections at the cost of choppier
+    /// motion, trails still fall at the same speed.
+    pub render_interval: usize,
+    pub render_mode: RenderMode,
+    /// colors the terminal supports, used by the default crossterm renderer
+    pub color_depth: ColorDepth,
+    /// what the default crossterm renderer clears before the first frame
+    pub initial_clear: InitialClear,
+    /// no new generators spawn while at least this many cells are lit,
+    /// caps the work `render` has to do on huge terminals
+    pub max_lit_cells: Option<usize>,
+    /// fraction of 0.0 to 1.0 of columns picked at startup to never spawn,
+    /// leaving persistent gaps in the rain
+    pub dead_column_fraction: f32,
+    /// frames `Waterfall::run` keeps animating without spawning after being
+    /// stopped, so the screen fades out instead of cutting off
+    pub graceful_exit_frames: usize,
+    /// degrees every generator shifts its hue per row it falls, so each
+    /// trail runs through colors top to bottom, 0.0 keeps `base_color`
+    pub hue_delta: f32,
+    /// range of rows per step a new generator falls at
+    pub speed: (f32, f32),
+    pub speed_dist: SpeedDist,
+    /// probability of 0.0 to 1.0 per head and step to light a bright spark
+    /// in a blank cell next to it, which fades right away
+    pub sparkle_rate: f64,
+    /// heads stop at the floor and stay lit, so the rain piles up and the
+    /// floor rises, see `Waterfall::reset_pileup`
+    pub enable_pileup: bool,
+    /// rows above the bottom of the screen the pile starts at
+    pub pileup_floor: usize,
+    /// adjacent columns every generator drives in sync, spans running into
+    /// the right edge are cut short
+    pub trail_thickness: usize,
+    /// probability of 0.0 to 1.0 per lit cell and frame to render it boosted
+    /// toward white, only affects rendering and leaves the simulation alone
+    pub shimmer_rate: f32,
+    /// draw the shimmer from `seed` when it's set, otherwise it differs
+    /// between runs even with the same seed
+    pub seeded_shimmer: bool,
+    /// degrees new trails slant away from straight down, positive leans
+    /// right, see `Waterfall::set_angle`
+    pub angle: f32,
+    /// period of a slow sine wave spawn density and brightness breathe
+    /// along, measured in steps of `fps` so it follows `run` in real time
+    pub intensity_cycle: Option<Duration>,
+    /// period and depth from 0.0 to 1.0 of a sine wave the trail color's
+    /// brightness throbs along, like a heartbeat, heads keep theirs. Counts
+    /// steps like `intensity_cycle` but only changes what is drawn.
+    pub base_pulse: Option<(Duration, f32)>,
+    /// Simulates on a grid of this many columns and rows no matter the
+    /// terminal size and samples it onto the screen when rendering, so
+    /// density and trail lengths look the same everywhere. Rows count
+    /// terminal rows, `RenderMode::SubCell` doubles them like usual.
+    pub virtual_size: Option<(usize, usize)>,
+    /// way the rain runs, pileup only builds up with `Direction::Down`
+    pub direction: Direction,
+    /// flips `direction` this often, running generators carry on in the
+    /// new direction from where they are
+    pub reverse_interval: Option<Duration>,
+    /// ordered dithering of faded colors, trades banding on slow fades for
+    /// a fixed fine noise pattern
+    pub dither: bool,
+    /// rows a generator moves before it retires even short of the edge,
+    /// keeps trails the same length on tall terminals
+    pub max_trail_travel: Option<usize>,
+    /// probability of 0.0 to 1.0 per generator and step to hold still for
+    /// the step, for a stuttering fall
+    pub hesitation_rate: f64,
+    /// color trails are written in
+    pub trail_color: (u8, u8, u8),
+    /// color heads are drawn in
+    pub head_color: (u8, u8, u8),
+    /// what `Waterfall::from_config` does when stdout isn't a terminal
+    pub non_terminal_output: NonTerminalOutput,
+    /// free columns kept between a new generator and any running one, 0
+    /// lets streams spawn right next to each other
+    pub min_column_spacing: usize,
+    /// 0.0 to 1.0, share of `density` quiet columns hand to the neighbours
+    /// of recent spawns, which sends waves of rain across the screen
+    /// without changing how much rain falls overall, 0.0 keeps columns
+    /// independent
+    pub wave_coupling: f32,
+    /// rows per step every generator speeds up by each step, so streams
+    /// start slow and rush toward the end, skipped rows are still written
+    pub gravity: f32,
+    /// Draws every non-ASCII glyph as a character of `ascii_fallback`
+    /// picked by its code point, so the same glyph always turns into the
+    /// same fallback. Only changes what `render` draws, the simulation
+    /// keeps its alphabet. The `RenderMode::SubCell` block is left alone.
+    pub ascii_only: bool,
+    pub ascii_fallback: String,
+    pub spawn_origin: SpawnOrigin,
+    pub blank_strategy: BlankStrategy,
+    /// runs every alphabet through `Waterfall::filter_renderable`
+    pub filter_unrenderable: bool,
+    /// steps a fully faded rune lingers as a dim ghost before its cell
+    /// blanks, softens the end of trails, 0 blanks right away
+    pub afterglow_frames: usize,
+    /// up to how many rows above the visible edge new generators start, so
+    /// heads don't enter in a flat line, 0 starts them all right on the edge
+    pub spawn_offset_jitter: usize,
+    /// times a generator turns around at the far edge and travels back
+    /// before it retires, 0 lets trails leave the grid like usual
+    pub bounces: usize,
+    /// shortens the rune lifetime of faster generators so every trail
+    /// covers about as many rows as one falling at the low end of `speed`
+    pub constant_trail_length: bool,
+    /// whether `Waterfall::set_symbols` also gives the runes already on
+    /// screen new glyphs from the new alphabet
+    pub reroll_on_symbol_change: bool,
+    /// draws glyphs that have a mirrored look alike in Unicode, like `E`
+    /// and `Ǝ`, mirrored. Only changes what `render` draws.
+    pub mirror_glyphs: bool,
+    /// share of the `Waterfall::pulse_columns` boost a column keeps from
+    /// one step to the next, 0.0 to 1.0
+    pub pulse_decay: f32,
+    /// glyph drawn in the blank gaps between a head and the end of its
+    /// trail to trace the stream's path, like `'│'`, `None` leaves gaps
+    /// blank. Slanted and sideways streams get none. Only changes what
+    /// `render` draws.
+    pub connector_glyph: Option<char>,
+    /// brightness of connectors relative to the trail color, 0.0 to 1.0
+    pub connector_intensity: f32,
+    /// relative odds of every new generator running each way, like
+    /// `vec![(StreamDirection::Straight, 8.0), (StreamDirection::DiagonalLeft, 1.0)]`,
+    /// empty runs every stream straight
+    pub direction_weights: Vec<(StreamDirection, f32)>,
+    /// only redraw cells that changed since the last frame
+    pub differential_render: bool,
+    /// with `differential_render`, also skip cells whose color moved by no
+    /// more than this per channel, trades a little fade smoothness for
+    /// less output, 0 redraws every changed cell
+    pub color_epsilon: u8,
+    /// terminal column, row, width and height the rain is drawn in, `None`
+    /// covers the whole terminal
+    pub region: Option<(u16, u16, u16, u16)>,
+    /// share the terminal with the host's own output: the cursor is put
+    /// back after every frame and nothing outside of the rain is cleared,
+    /// best paired with `region`
+    pub confined: bool,
+    /// top and bottom color every trail is recolored with by row, so the
+    /// whole screen grades from one to the other, heads keep `head_color`.
+    /// Only changes what is drawn, fading applies on top.
+    pub height_gradient: Option<[(u8, u8, u8); 2]>,
+    /// only uses this many random distinct characters of `symbols`, for a
+    /// calmer rain, see `Waterfall::reroll_alphabet`
+    pub alphabet_limit: Option<usize>,
+    /// simulates only every `coarse_factor`th row and stretches the result
+    /// over the screen, cuts the cost of huge terminals at the price of
+    /// vertical resolution, 1 simulates every row
+    pub coarse_factor: usize,
+    /// probability of 0.0 to 1.0 per written rune to get stuck bright for
+    /// `stuck_glyph_frames` steps before it starts fading, for scattered
+    /// glyphs lingering behind the flow
+    pub stuck_glyph_rate: f64,
+    /// extra steps a stuck rune stays at full brightness
+    pub stuck_glyph_frames: u8,
+    /// leaves out everything that flickers or flashes whatever its own
+    /// setting: ignition flicker, animated heads, sparkles, shimmer and
+    /// `Waterfall::flash`
+    pub reduced_motion: bool,
+    /// trail colors handed to new generators in turn, empty gives every
+    /// trail `trail_color`, see `Waterfall::set_palette`
+    pub palette: Vec<(u8, u8, u8)>,
+    /// tint every lit cell is blended toward and by how much from 0.0 to
+    /// 1.0, see `Waterfall::set_color_grade`
+    pub color_grade: ((u8, u8, u8), f32),
+}
+
+impl WaterfallConfig {
+    // grid rows simulated to fill `height` terminal rows
+    pub(crate) fn simulated_rows(&self, height: usize) -> usize {
+        self.render_mode
+            .grid_rows(height)
+            .div_ceil(self.coarse_factor.max(1))
+    }
+
+    /// Takes the settings that only apply when a waterfall is built from
+    /// `current`: `render_mode`, `virtual_size`, `coarse_factor`, `region`,
+    /// `confined`, `seed`, `fps`, `min_size`, `restore_on_panic`,
+    /// `color_depth`, `initial_clear`, `differential_render`,
+    /// `color_epsilon`, `blank_strategy` and `non_terminal_output`.
+    pub fn keep_rebuild_settings(&mut self, current: &WaterfallConfig) {
+        self.render_mode = current.render_mode;
+        self.virtual_size = current.virtual_size;
+        self.coarse_factor = current.coarse_factor;
+        self.region = current.region;
+        self.confined = current.confined;
+        self.seed = current.seed;
+        self.fps = current.fps;
+        self.min_size = current.min_size;
+        self.restore_on_panic = current.restore_on_panic;
+        self.color_depth = current.color_depth;
+        self.initial_clear = current.initial_clear;
+        self.differential_render = current.differential_render;
+        self.color_epsilon = current.color_epsilon;
+        self.blank_strategy = current.blank_strategy;
+        self.non_terminal_output = current.non_terminal_output;
+    }
+
+    /// As close to the film as a terminal gets: its katakana and digits,
+    /// green trails with bright heads and mirrored glyphs where possible.
+    pub fn film() -> Self {
+        let (trail_color, head_color) = ColorMode::Matrix.colors();
+        WaterfallConfig {
+            symbols: FILM_SYMBOLS.to_string(),
+            trail_color,
+            head_color,
+            mirror_glyphs: true,
+            ..Default::default()
+        }
+    }
+
+    /// For people sensitive to motion: sparse trails falling slowly and
+    /// `reduced_motion` set, see `Waterfall::prefers_reduced_motion`.
+    pub fn reduced_motion() -> Self {
+        WaterfallConfig {
+            density: REDUCED_MOTION_DENSITY,
+            speed: (REDUCED_MOTION_SPEED, REDUCED_MOTION_SPEED),
+            reduced_motion: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for WaterfallConfig {
+    fn default() -> Self {
+        WaterfallConfig {
+            symbols: SYMBOLS.to_string(),
+            zero_width_symbols: ZeroWidthSymbols::Reject,
+            density: GENERATOR_DENSITY,
+            spawn_bias: 0.0,
+            normalize_density_by_width: false,
+            min_size: MIN_TERMINAL_SIZE,
+            restore_on_panic: false,
+            seed: None,
+            ignition_flicker: false,
+            head_glyph: None,
+            animate_heads: false,
+            fps: FPS,
+            refresh_interval: None,
+            render_interval: 1,
+            render_mode: RenderMode::Glyph,
+            color_depth: ColorDepth::TrueColor,
+            initial_clear: InitialClear::All,
+            max_lit_cells: None,
+            dead_column_fraction: 0.0,
+            graceful_exit_frames: 0,
+            hue_delta: 0.0,
+            speed: (1.0, 1.0),
+            speed_dist: SpeedDist::Uniform,
+            sparkle_rate: 0.0,
+            enable_pileup: false,
+            pileup_floor: 0,
+            trail_thickness: 1,
+            shimmer_rate: 0.0,
+            seeded_shimmer: true,
+            angle: 0.0,
+            intensity_cycle: None,
+            base_pulse: None,
+            virtual_size: None,
+            direction: Direction::Down,
+            reverse_interval: None,
+            dither: false,
+            max_trail_travel: None,
+            hesitation_rate: 0.0,
+            trail_color: RUNE_COLOR_BASE,
+            head_color: RUNE_GENERATOR_COLOR,
+            non_terminal_output: NonTerminalOutput::Error,
+            min_column_spacing: 0,
+            wave_coupling: 0.0,
+            gravity: 0.0,
+            ascii_only: false,
+            ascii_fallback: ASCII_FALLBACK.to_string(),
+            spawn_origin: SpawnOrigin::All,
+            blank_strategy: BlankStrategy::PerCell,
+            filter_unrenderable: false,
+            afterglow_frames: 0,
+            spawn_offset_jitter: 0,
+            bounces: 0,
+            constant_trail_length: false,
+            reroll_on_symbol_change: false,
+            mirror_glyphs: false,
+            pulse_decay: 0.9,
+            connector_glyph: None,
+            connector_intensity: 0.15,
+            direction_weights: vec![],
+            differential_render: false,
+            color_epsilon: 0,
+            region: None,
+            confined: false,
+            height_gradient: None,
+            alphabet_limit: None,
+            coarse_factor: 1,
+            stuck_glyph_rate: 0.0,
+            stuck_glyph_frames: 30,
+            reduced_motion: false,
+            palette: vec![],
+            color_grade: ((0, 0, 0), 0.0),
+        }
+    }
+}
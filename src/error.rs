@@ -0,0 +1,98 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Result of every fallible `Waterfall` and `Renderer` call.
+pub type Result<T, E = WaterfallError> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while setting up or driving a `Waterfall`.
+#[derive(Debug)]
+pub enum WaterfallError {
+    /// the terminal is smaller than `min_size`
+    TerminalSize {
+        width: u16,
+        height: u16,
+        min_width: u16,
+        min_height: u16,
+    },
+    /// stdout is redirected and `non_terminal_output` is
+    /// `NonTerminalOutput::Error`
+    NotATerminal,
+    /// a cell outside of the `width` by `height` grid or screen, `y` is
+    /// `None` for whole columns
+    OutOfBounds {
+        x: usize,
+        y: Option<usize>,
+        width: usize,
+        height: usize,
+    },
+    /// a setting or argument that can't be used, like an alphabet without
+    /// printable characters
+    InvalidConfig(String),
+    /// the symbols file couldn't be read or isn't valid UTF-8
+    SymbolsFile { path: PathBuf, source: io::Error },
+    /// writing to the terminal or another target failed
+    Io(io::Error),
+    /// failure reported by a custom `Renderer`
+    Render(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for WaterfallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaterfallError::TerminalSize {
+                width,
+                height,
+                min_width,
+                min_height,
+            } => write!(
+                f,
+                "terminal too small: need at least {}x{}, got {}x{}",
+                min_width, min_height, width, height
+            ),
+            WaterfallError::NotATerminal => write!(
+                f,
+                "stdout is not a terminal, use NonTerminalOutput::PlainText to write text frames"
+            ),
+            WaterfallError::OutOfBounds {
+                x, y: None, width, ..
+            } => write!(f, "column {} is outside of {} wide grid", x, width),
+            WaterfallError::OutOfBounds {
+                x,
+                y: Some(y),
+                width,
+                height,
+            } => write!(
+                f,
+                "cell {}x{} is outside of {}x{} grid",
+                x, y, width, height
+            ),
+            WaterfallError::InvalidConfig(message) => write!(f, "{}", message),
+            WaterfallError::SymbolsFile { path, source } => {
+                write!(
+                    f,
+                    "failed to read symbols file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            WaterfallError::Io(error) => write!(f, "{}", error),
+            WaterfallError::Render(error) => write!(f, "renderer failed: {}", error),
+        }
+    }
+}
+
+impl Error for WaterfallError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WaterfallError::SymbolsFile { source, .. } => Some(source),
+            WaterfallError::Io(error) => Some(error),
+            WaterfallError::Render(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WaterfallError {
+    fn from(error: io::Error) -> Self {
+        WaterfallError::Io(error)
+    }
+}
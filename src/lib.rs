@@ -1,51 +1,258 @@
 use std::{
-    io::{self, Stdout, Write},
-    ops::{Deref, DerefMut},
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, IsTerminal},
+    mem,
+    ops::{Index, IndexMut, Range, RangeInclusive},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use anyhow::Result;
 use crossterm::{
-    cursor,
-    style::{self, Stylize},
-    terminal::{self, Clear, ClearType},
-    QueueableCommand,
+    event::{self, Event},
+    terminal, ExecutableCommand,
 };
+use rand::{rngs::StdRng, seq::index, Rng, SeedableRng};
+use unicode_width::UnicodeWidthChar;
 
-use crossterm::style::{Attribute, Color};
-use rand::Rng;
+// forward to the `log` crate under the `symbol_waterfall` target when the
+// `log` feature is on and compile to nothing otherwise
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::debug!(target: "symbol_waterfall", $($arg)*);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::trace!(target: "symbol_waterfall", $($arg)*);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+mod clock;
+mod color;
+mod compositor;
+mod config;
+mod error;
+mod player;
+mod renderer;
+mod scene;
+mod stream;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use compositor::{BlendMode, Compositor};
+pub use config::{
+    ColorMode, Direction, RenderMode, SpawnOrigin, SpeedDist, StreamDirection, WaterfallConfig,
+    ZeroWidthSymbols,
+};
+pub use error::{Result, WaterfallError};
+pub use player::Player;
+use renderer::NullRenderer;
+pub use renderer::{
+    BlankStrategy, ColorDepth, CrosstermRenderer, InitialClear, NonTerminalOutput, Renderer,
+    TextRenderer,
+};
+pub use scene::{Action, Scene};
+pub use stream::FrameStreamWriter;
 
 const SYMBOLS: &str = "ﾊﾐﾋｰｳｼﾅﾓﾆｻﾜﾂｵﾘｱﾎﾃﾏｹﾒｴｶｷﾑﾕﾗｾﾈｽﾀﾇﾍｦｲｸｺｿﾁﾄﾉﾌﾔﾖﾙﾚﾛﾝ012345789Z:.\"=*+-<>¦╌ç";
 // range of how long it takes for a rune to start fading
 const RUNE_LIFETIME: (u8, u8) = (4, 20);
 // how long it takes for a rune to fade
 const RUNE_FADE_DURATION: u8 = 7;
-// probability of .0 to .1 that generator spawns in a column per step
-const GENERATOR_IN_COLUMN: (u16, u16) = (1, 90);
+// default probability of .0 to .1 that generator spawns in a column per step
+const GENERATOR_DENSITY: f64 = 2.0 / 90.0;
+// frames per second of `Waterfall::run`
+const FPS: u32 = 20;
+// width at which a normalized density spawns exactly `density` per column
+const DENSITY_REFERENCE_WIDTH: usize = 80;
+// smallest terminal (columns, rows) the animation makes sense on
+const MIN_TERMINAL_SIZE: (u16, u16) = (4, 3);
 const RUNE_COLOR_BASE: (u8, u8, u8) = (0, 255, 255);
 const RUNE_GENERATOR_COLOR: (u8, u8, u8) = (255, 0, 0);
+// packs two grid rows into one terminal cell in `RenderMode::SubCell`
+const SUB_CELL_GLYPH: char = '▀';
+const SPARKLE_COLOR: (u8, u8, u8) = (255, 255, 255);
+// depth of the farthest column, 1.0 being the front
+const MIN_COLUMN_DEPTH: f32 = 0.1;
+// most steps a new generator flickers dim before burning at full brightness
+const IGNITION_STEPS: u8 = 2;
+// range of brightness a flickering generator head is drawn at
+const IGNITION_BRIGHTNESS: (f32, f32) = (0.2, 0.7);
+// how far a shimmering cell is pulled toward white, 1.0 being all the way
+const SHIMMER_BOOST: f32 = 0.6;
+// how far the first frame of a flash pulls every cell toward its tint
+const FLASH_STRENGTH: f32 = 0.7;
+// slowest speed `constant_trail_length` measures trails against, keeps
+// near still generators from cutting every other trail down to nothing
+const MIN_TRAIL_SPEED: f32 = 0.1;
+// brightness a ghost left by `afterglow_frames` starts out at
+const AFTERGLOW_BRIGHTNESS: f32 = 0.15;
+// how far lit cells of a highlighted column are pulled toward its color
+const HIGHLIGHT_STRENGTH: f32 = 0.8;
+// steepest slant in degrees away from straight down `set_angle` accepts
+const MAX_RAIN_ANGLE: f32 = 60.0;
+// lowest and highest point `intensity_cycle` swings density and brightness
+// between
+const INTENSITY_RANGE: (f32, f32) = (0.3, 1.0);
+// frame size used for plain text output when the size can't be queried
+const PLAIN_TEXT_SIZE: (u16, u16) = (80, 24);
+// alphabet of `WaterfallConfig::film`, the half-width katakana, digits and
+// signs of the film titles without the glyphs fonts tend to lack
+const FILM_SYMBOLS: &str = "ﾊﾐﾋｰｳｼﾅﾓﾆｻﾜﾂｵﾘｱﾎﾃﾏｹﾒｴｶｷﾑﾕﾗｾﾈｽﾀﾇﾍ012345789Z:.\"=*+-<>¦|";
+// rows per step and spawn probability of `WaterfallConfig::reduced_motion`
+const REDUCED_MOTION_SPEED: f32 = 0.25;
+const REDUCED_MOTION_DENSITY: f64 = GENERATOR_DENSITY / 3.0;
+// glyphs `mirror_glyphs` swaps for a mirrored look alike, katakana have
+// none and are drawn as they are
+const MIRRORED_GLYPHS: &[(char, char)] = &[
+    ('<', '>'),
+    ('>', '<'),
+    ('(', ')'),
+    (')', '('),
+    ('[', ']'),
+    (']', '['),
+    ('/', '\\'),
+    ('\\', '/'),
+    ('3', 'Ɛ'),
+    ('C', 'Ɔ'),
+    ('E', 'Ǝ'),
+    ('N', 'И'),
+    ('R', 'Я'),
+    ('S', 'Ƨ'),
+];
+// glyphs `ascii_only` substitutes for the rest
+const ASCII_FALLBACK: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ:.\"=*+-<>|";
+// frames per grid row `play_text` waits for the rain to uncover its text
+const MESSAGE_REVEAL_FRAMES: usize = 4;
+// columns to each side a spawn excites with `wave_coupling`
+const WAVE_RADIUS: usize = 2;
+// share of its excitement a column keeps from one step to the next
+const WAVE_DECAY: f32 = 0.8;
+// spawn probability a column gets on top of `density` right after
+// `pulse_columns`, wears off with `pulse_decay`
+const PULSE_DENSITY: f64 = 0.3;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Rune {
     character: char,
     lifetime: u8,
     color: (u8, u8, u8),
 }
-struct Characters(&'static str);
+
+/// Read only state of one grid cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RuneView {
+    /// `' '` for blank cells
+    pub glyph: char,
+    /// color of the rune before fading
+    pub color: (u8, u8, u8),
+    /// fraction of `color` shown this frame, 0.0 to 1.0
+    pub brightness: f32,
+}
+
+impl RuneView {
+    pub fn displayed_color(&self) -> (u8, u8, u8) {
+        scale_color(self.color, self.brightness)
+    }
+}
+
+// code points common monospace terminal fonts tend to lack or draw
+// misaligned: dashed box drawing lines, the halfwidth katakana sound marks
+// that are meant to combine with the glyph before them, variation
+// selectors and the private use area icon fonts live in
+const UNRELIABLE_GLYPHS: &[RangeInclusive<char>] = &[
+    '\u{2504}'..='\u{250B}',
+    '\u{254C}'..='\u{254F}',
+    '\u{FF9E}'..='\u{FF9F}',
+    '\u{FE00}'..='\u{FE0F}',
+    '\u{E000}'..='\u{F8FF}',
+];
+
+fn is_renderable(c: char) -> bool {
+    !UNRELIABLE_GLYPHS.iter().any(|range| range.contains(&c))
+}
+
+#[derive(Clone)]
+struct Characters(Vec<char>);
 
 impl Characters {
-    fn create_random_rune(&self, color: (u8, u8, u8)) -> Rune {
-        let mut rng = rand::thread_rng();
-        let chars: Vec<char> = self.0.chars().collect();
-        let idx = rng.gen_range(0..chars.len());
-        let symbol = chars[idx];
+    // alphabet out of `symbols`, never empty
+    fn new(symbols: &str, config: &WaterfallConfig) -> Result<Self> {
+        let mut chars = Vec::new();
+        for c in symbols.chars() {
+            if config.filter_unrenderable && !is_renderable(c) {
+                continue;
+            }
+            if c.width().is_some_and(|width| width > 0) {
+                chars.push(c);
+            } else if config.zero_width_symbols == ZeroWidthSymbols::Reject {
+                return Err(WaterfallError::InvalidConfig(format!(
+                    "symbol {:?} (U+{:04X}) has no width and can't fill a cell",
+                    c, c as u32
+                )));
+            }
+        }
+
+        if chars.is_empty() {
+            return Err(WaterfallError::InvalidConfig(
+                "symbols contain no printable characters".to_string(),
+            ));
+        }
+        Ok(Characters(chars))
+    }
+
+    // keeps `limit` random distinct characters, or all of them without one
+    fn limited(self, limit: Option<usize>, rng: &mut impl Rng) -> Self {
+        let Some(limit) = limit else {
+            return self;
+        };
+        let mut seen = HashSet::new();
+        let distinct: Vec<char> = self.0.into_iter().filter(|&c| seen.insert(c)).collect();
+        let limit = limit.clamp(1, distinct.len());
+        let mut picked = index::sample(rng, distinct.len(), limit).into_vec();
+        picked.sort_unstable();
+        Characters(picked.into_iter().map(|i| distinct[i]).collect())
+    }
 
-        self.create_rune(symbol, color)
+    fn random_char(&self, rng: &mut impl Rng) -> char {
+        let idx = rng.gen_range(0..self.0.len());
+        self.0[idx]
     }
 
-    fn create_rune(&self, character: char, color: (u8, u8, u8)) -> Rune {
-        let mut rng = rand::thread_rng();
+    fn create_random_rune(&self, rng: &mut impl Rng, color: (u8, u8, u8), depth: f32) -> Rune {
+        let symbol = self.random_char(rng);
+
+        self.create_rune(rng, symbol, color, depth)
+    }
 
-        let lifetime = rng.gen_range(RUNE_LIFETIME.0..RUNE_LIFETIME.1) + RUNE_FADE_DURATION;
+    fn create_rune(
+        &self,
+        rng: &mut impl Rng,
+        character: char,
+        color: (u8, u8, u8),
+        depth: f32,
+    ) -> Rune {
+        let lifetime = (rng.gen_range(RUNE_LIFETIME.0..RUNE_LIFETIME.1) as f32 * depth).round()
+            as u8
+            + fade_duration(depth);
         Rune {
             character,
             lifetime,
@@ -53,153 +260,2924 @@ impl Characters {
         }
     }
 }
-struct Grid(Vec<Vec<Rune>>);
 
-impl Deref for Grid {
-    type Target = Vec<Vec<Rune>>;
+// per cell values laid out row after row like the grid, moved onto a grid
+// of `width` by `height` the way `Grid::resize` moves runes
+fn resize_cells(cells: &[u8], old_width: usize, width: usize, height: usize) -> Vec<u8> {
+    let mut resized = vec![0; width * height];
+    for (i, &value) in cells.iter().enumerate() {
+        let (x, y) = (i % old_width.max(1), i / old_width.max(1));
+        if x < width && y < height {
+            resized[y * width + x] = value;
+        }
+    }
+    resized
+}
+
+// alphabet new runes of column `x` are picked from, see
+// `Waterfall::set_column_alphabets`
+fn column_alphabet<'a>(
+    columns: &'a [Option<Characters>],
+    characters: &'a Characters,
+    x: usize,
+) -> &'a Characters {
+    columns
+        .get(x)
+        .and_then(Option::as_ref)
+        .unwrap_or(characters)
+}
+
+fn mirrored(c: char) -> char {
+    MIRRORED_GLYPHS
+        .iter()
+        .find(|&&(from, _)| from == c)
+        .map_or(c, |&(_, to)| to)
+}
+
+// seed of the rng stream for step `step`, splitmix64 of the pair
+fn step_seed(seed: u64, step: u64) -> u64 {
+    let mut z = seed ^ step.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// how many steps runes in a column of given depth take to fade
+fn fade_duration(depth: f32) -> u8 {
+    ((RUNE_FADE_DURATION as f32 * depth).round() as u8).max(1)
+}
+
+fn scale_color(color: (u8, u8, u8), factor: f32) -> (u8, u8, u8) {
+    (
+        (color.0 as f32 * factor).round() as u8,
+        (color.1 as f32 * factor).round() as u8,
+        (color.2 as f32 * factor).round() as u8,
+    )
+}
+
+// moves `color` toward `target`, 0.0 keeps it and 1.0 replaces it
+fn blend(color: (u8, u8, u8), target: (u8, u8, u8), amount: f32) -> (u8, u8, u8) {
+    let channel = |c: u8, t: u8| (c as f32 + (t as f32 - c as f32) * amount).round() as u8;
+    (
+        channel(color.0, target.0),
+        channel(color.1, target.1),
+        channel(color.2, target.2),
+    )
+}
+
+// 4x4 ordered dither thresholds, spread evenly over 0..16
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// like `scale_color` but nudges every channel by up to half a step before
+// rounding, depending only on the cell position, so gradients dither
+// instead of banding
+fn dither_color(color: (u8, u8, u8), factor: f32, x: usize, y: usize) -> (u8, u8, u8) {
+    let offset = (BAYER_MATRIX[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+    let channel = |c: u8| (c as f32 * factor + offset).round().clamp(0.0, 255.0) as u8;
+    (channel(color.0), channel(color.1), channel(color.2))
+}
+
+// nearest neighbour resampling of `cells` to `width` by `height`
+fn sample<T: Copy>(cells: &[Vec<T>], width: usize, height: usize) -> Vec<Vec<T>> {
+    let source_height = cells.len();
+    let source_width = cells.first().map_or(0, |row| row.len());
+    if source_width == 0 || source_height == 0 {
+        return vec![vec![]; height];
+    }
+
+    (0..height)
+        .map(|y| {
+            let row = &cells[y * source_height / height];
+            (0..width).map(|x| row[x * source_width / width]).collect()
+        })
+        .collect()
+}
+
+// rows stored back to back in one allocation, blank cells cost the same
+// as lit ones but there's no per row overhead and `step` walks memory in
+// order
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Grid {
+    cells: Vec<Rune>,
+    width: usize,
+    height: usize,
+}
+
+impl Index<usize> for Grid {
+    type Output = [Rune];
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    fn index(&self, y: usize) -> &[Rune] {
+        &self.cells[y * self.width..(y + 1) * self.width]
     }
 }
 
-impl DerefMut for Grid {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+impl IndexMut<usize> for Grid {
+    fn index_mut(&mut self, y: usize) -> &mut [Rune] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
     }
 }
 
 impl Grid {
-    fn new(characters: &Characters) -> Result<Self> {
-        let (width, height) = terminal::size()?;
-        let rune = characters.create_rune(' ', RUNE_COLOR_BASE);
-        Ok(Grid(vec![vec![rune; width as usize]; height as usize]))
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn new(width: usize, height: usize, characters: &Characters, rng: &mut impl Rng) -> Self {
+        let rune = characters.create_rune(rng, ' ', RUNE_COLOR_BASE, 1.0);
+        Grid {
+            cells: vec![rune; width * height],
+            width,
+            height,
+        }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[Rune]> {
+        (0..self.height).map(|y| &self[y])
+    }
+
+    fn cells_mut(&mut self) -> impl Iterator<Item = &mut Rune> {
+        self.cells.iter_mut()
+    }
+
+    fn cells(&self) -> impl Iterator<Item = &Rune> {
+        self.cells.iter()
+    }
+
+    // keeps the cells that still fit, new ones are filled with `blank`
+    fn resize(&mut self, width: usize, height: usize, blank: Rune) {
+        let mut cells = vec![blank; width * height];
+        for y in 0..height.min(self.height) {
+            let kept = width.min(self.width);
+            cells[y * width..y * width + kept].clone_from_slice(&self[y][..kept]);
+        }
+        *self = Grid {
+            cells,
+            width,
+            height,
+        };
     }
 
     fn set_rune(&mut self, x: usize, y: usize, rune: Rune) -> Result<()> {
-        *self
-            .0
-            .get_mut(y)
-            .expect("out of bounds y Grid index")
-            .get_mut(x)
-            .expect("out of bounds x Grid index") = rune;
+        *self.get_rune(x, y)? = rune;
         Ok(())
     }
 
     fn get_rune(&mut self, x: usize, y: usize) -> Result<&mut Rune> {
-        Ok(self
-            .0
-            .get_mut(y)
-            .expect("out of bounds y Grid index")
-            .get_mut(x)
-            .expect("out of bounds y Grid index"))
+        assert!(y < self.height, "out of bounds y Grid index");
+        assert!(x < self.width, "out of bounds x Grid index");
+        Ok(&mut self.cells[y * self.width + x])
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Generator {
+    x: usize,
+    y: usize,
+    // columns driven in sync starting at x, already clipped to the grid
+    span: usize,
+    // steps left until the head stops flickering
+    ignition: u8,
+    ignition_brightness: f32,
+    // color the trail is written in, drifts with `hue_delta`
+    color: (u8, u8, u8),
+    hue: f32,
+    // rows per step, fractions accumulate in progress
+    speed: f32,
+    progress: f32,
+    // added to speed every step
+    acceleration: f32,
+    // columns moved per row, fractions accumulate in drift
+    dx: f32,
+    drift: f32,
+    // left the grid through a side and is dropped after the step
+    retired: bool,
+    // rows moved since spawning
+    traveled: usize,
+    // rows left to fall above the visible edge before the head shows up
+    pre_roll: usize,
+    // heading against `direction` after an odd number of bounces
+    reversed: bool,
+    // times left to turn around at the far edge instead of retiring
+    bounces: usize,
+    // columns moved per step by streams running across the screen instead
+    // of along it, 0 for every other stream
+    sideways: isize,
+}
+
+impl Generator {
+    fn new(x: usize, color: (u8, u8, u8)) -> Self {
+        Generator {
+            x,
+            y: 0,
+            span: 1,
+            ignition: 0,
+            ignition_brightness: 1.0,
+            color,
+            hue: color::rgb_to_hsv(color).0,
+            speed: 1.0,
+            progress: 0.0,
+            acceleration: 0.0,
+            dx: 0.0,
+            drift: 0.0,
+            retired: false,
+            traveled: 0,
+            pre_roll: 0,
+            reversed: false,
+            bounces: 0,
+            sideways: 0,
+        }
     }
+
+    fn columns(&self) -> Range<usize> {
+        self.x..self.x + self.span
+    }
+
+    // whether the head made it past the pre-roll onto the grid
+    fn visible(&self) -> bool {
+        self.pre_roll == 0
+    }
+
+    fn heading(&self, direction: Direction) -> Direction {
+        if self.reversed {
+            direction.reversed()
+        } else {
+            direction
+        }
+    }
+}
+
+// crossfade between two configs started by `Waterfall::transition_to`
+struct Transition {
+    from: WaterfallConfig,
+    to: WaterfallConfig,
+    steps: u64,
+    done: u64,
+    // whether the fields that can't be blended took their new value yet
+    switched: bool,
+}
+
+// fixed text painted over the rain
+struct Overlay {
+    x: usize,
+    y: usize,
+    text: String,
+    color: (u8, u8, u8),
+}
+
+/// Frame sent by the thread of `Waterfall::spawn_simulator`.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// step index the frame was taken at
+    pub step: u64,
+    /// the grid as `frame_to_string` returns it
+    pub text: String,
+}
+
+/// Complete simulation state of a `Waterfall`, taken with
+/// `Waterfall::snapshot` and put back with `Waterfall::restore`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WaterfallSnapshot {
+    grid: Grid,
+    generators: Vec<Generator>,
 }
 
-pub struct Waterfall<T: Write = Stdout> {
+/// Render time color remap, see `Waterfall::set_color_filter`.
+pub type ColorFilter = dyn Fn((u8, u8, u8), usize, usize) -> (u8, u8, u8) + Send;
+
+pub struct Waterfall {
     grid: Grid,
-    writer: T,
-    generators: Vec<(usize, usize)>,
+    // terminal cells the frame is drawn onto, only differs from the grid
+    // with `virtual_size`
+    output: (usize, usize),
+    renderer: Box<dyn Renderer + Send>,
+    generators: Vec<Generator>,
     characters: Characters,
-    base_color: (u8, u8, u8),
+    // alphabets of single columns taking over from `characters`, see
+    // `set_column_alphabets`
+    column_alphabets: Vec<Option<Characters>>,
+    rng: StdRng,
+    // only drives render time effects so they never shift the simulation
+    render_rng: StdRng,
+    // per column, 1.0 is the front and lower values are farther away
+    depth: Vec<f32>,
+    // cells generators never write to
+    mask: HashSet<(usize, usize)>,
+    overlays: Vec<Overlay>,
+    color_filter: Option<Box<ColorFilter>>,
+    // cells piled up per column with `enable_pileup`
+    pile: Vec<usize>,
+    // columns that never spawn generators
+    dead_columns: Vec<bool>,
+    // columns whose generators and runes are paused
+    frozen_columns: Vec<bool>,
+    // per cell frames left of the dim ghost a faded rune leaves behind,
+    // row after row like the grid
+    afterglow: Vec<u8>,
+    // per cell steps left before a stuck rune resumes fading, laid out
+    // like `afterglow`
+    stuck: Vec<u8>,
+    // per column highlight color and frames left, see `highlight_column`
+    highlights: Vec<((u8, u8, u8), usize)>,
+    // column `SpawnOrigin::Random` spreads from
+    origin: usize,
+    // per column 0.0 to 1.0, how recently a spawn happened nearby
+    wave_heat: Vec<f32>,
+    // per column 0.0 to 1.0, what's left of the last `pulse_columns`
+    pulse: Vec<f32>,
+    // `palette` entry the next generator gets
+    palette_cursor: usize,
+    // whether step spawns new generators
+    spawning: bool,
+    stop: Arc<AtomicBool>,
+    // non-blank cells as of the end of the last step
+    lit_cells: usize,
+    // time handed to `update` that didn't add up to a whole step yet
+    pending_time: Duration,
+    // glyphs of the `play_text` message by cell and whether the rain
+    // uncovered them yet
+    message: HashMap<(usize, usize), (char, bool)>,
+    // tint, total and remaining frames of the running `flash`
+    flash: Option<((u8, u8, u8), usize, usize)>,
+    transition: Option<Transition>,
+    runes_created: u64,
+    generators_spawned: u64,
+    // steps taken since construction or the last `set_step_index`
+    steps: u64,
+    // `direction` at step 0, which `reverse_interval` flips from
+    start_direction: Direction,
+    config: WaterfallConfig,
 }
 
 impl Waterfall {
     pub fn new() -> Result<Self> {
-        let symbols = Characters(SYMBOLS);
-        let grid = Grid::new(&symbols)?;
-        let mut stdout = io::stdout();
+        Self::from_config(WaterfallConfig::default())
+    }
+
+    /// Best color depth the current terminal advertises, read from the
+    /// environment:
+    ///
+    /// - `COLORTERM` set to `truecolor` or `24bit` means `TrueColor`
+    /// - `TERM` unset, empty or `dumb` means `Monochrome`
+    /// - `TERM` ending in `-direct` means `TrueColor`
+    /// - `TERM` containing `256color` means `Ansi256`
+    /// - any other `TERM` gets `Ansi16`
+    pub fn detect_color_support() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            return ColorDepth::TrueColor;
+        }
+
+        let term = env::var("TERM").unwrap_or_default();
+        if term.is_empty() || term == "dumb" {
+            ColorDepth::Monochrome
+        } else if term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// Whether the environment asks for reduced motion, that is
+    /// `REDUCE_MOTION` is set to anything but empty, `0` or `false`.
+    pub fn prefers_reduced_motion() -> bool {
+        let value = env::var("REDUCE_MOTION").unwrap_or_default();
+        !matches!(value.as_str(), "" | "0" | "false")
+    }
+
+    /// Waterfall with the `WaterfallConfig::reduced_motion` preset.
+    pub fn reduced_motion() -> Result<Self> {
+        Self::from_config(WaterfallConfig::reduced_motion())
+    }
+
+    pub fn from_config(config: WaterfallConfig) -> Result<Self> {
+        if !io::stdout().is_terminal() {
+            match config.non_terminal_output {
+                NonTerminalOutput::Error => return Err(WaterfallError::NotATerminal),
+                NonTerminalOutput::PlainText => {
+                    let (width, height) = terminal::size().unwrap_or(PLAIN_TEXT_SIZE);
+                    let renderer = TextRenderer::new(io::stdout());
+                    return Self::with_size(
+                        config,
+                        Box::new(renderer),
+                        width as usize,
+                        height as usize,
+                    );
+                }
+            }
+        }
+
+        if config.restore_on_panic {
+            renderer::install_panic_hook();
+        }
+        let mut renderer = CrosstermRenderer::new()?;
+        renderer.set_color_depth(config.color_depth);
+        renderer.set_initial_clear(config.initial_clear);
+        renderer.set_blank_strategy(config.blank_strategy);
+        renderer.set_differential(config.differential_render);
+        renderer.set_color_epsilon(config.color_epsilon);
+        renderer.set_confined(config.confined);
+        if let Some((x, y, width, height)) = config.region {
+            renderer.set_region(x, y, width, height);
+            return Self::with_size(config, Box::new(renderer), width as usize, height as usize);
+        }
+        Self::with_renderer(config, renderer)
+    }
+
+    pub fn with_renderer(
+        config: WaterfallConfig,
+        renderer: impl Renderer + Send + 'static,
+    ) -> Result<Self> {
+        let (width, height) = terminal::size()?;
+        let (min_width, min_height) = config.min_size;
+        if width < min_width || height < min_height {
+            return Err(WaterfallError::TerminalSize {
+                width,
+                height,
+                min_width,
+                min_height,
+            });
+        }
+
+        Self::with_size(config, Box::new(renderer), width as usize, height as usize)
+    }
+
+    /// Draws with `renderer` onto `width` by `height` cells whatever the
+    /// terminal's size, for renderers like `FrameStreamWriter` that don't
+    /// draw to it.
+    pub fn with_renderer_and_size(
+        config: WaterfallConfig,
+        renderer: impl Renderer + Send + 'static,
+        width: usize,
+        height: usize,
+    ) -> Result<Self> {
+        Self::with_size(config, Box::new(renderer), width, height)
+    }
+
+    /// Simulation only instance of `width` by `height` cells that never
+    /// touches the terminal, `render` draws nowhere.
+    pub fn headless(config: WaterfallConfig, width: usize, height: usize) -> Result<Self> {
+        Self::with_size(config, Box::new(NullRenderer), width, height)
+    }
 
-        stdout.queue(cursor::Hide)?;
-        stdout.queue(Clear(ClearType::All))?;
+    fn with_size(
+        config: WaterfallConfig,
+        renderer: Box<dyn Renderer + Send>,
+        width: usize,
+        height: usize,
+    ) -> Result<Self> {
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let render_rng = match config.seed {
+            Some(seed) if config.seeded_shimmer => StdRng::seed_from_u64(!seed),
+            _ => StdRng::from_entropy(),
+        };
+        let symbols =
+            Characters::new(&config.symbols, &config)?.limited(config.alphabet_limit, &mut rng);
+        let output = (width, height);
+        let (width, height) = config.virtual_size.unwrap_or(output);
+        let rows = config.simulated_rows(height);
+        let grid = Grid::new(width, rows, &symbols, &mut rng);
+        let origin = match config.spawn_origin {
+            SpawnOrigin::Random => rng.gen_range(0..width.max(1)),
+            _ => 0,
+        };
 
-        Ok(Waterfall {
+        let mut waterfall = Waterfall {
             grid,
+            output,
             generators: vec![],
-            writer: stdout,
+            renderer,
             characters: symbols,
-            base_color: RUNE_COLOR_BASE,
-        })
+            column_alphabets: vec![None; width],
+            rng,
+            render_rng,
+            depth: vec![1.0; width],
+            mask: HashSet::new(),
+            overlays: vec![],
+            color_filter: None,
+            pile: vec![0; width],
+            dead_columns: vec![false; width],
+            frozen_columns: vec![false; width],
+            afterglow: vec![],
+            stuck: vec![],
+            highlights: vec![((0, 0, 0), 0); width],
+            origin,
+            wave_heat: vec![0.0; width],
+            pulse: vec![0.0; width],
+            palette_cursor: 0,
+            spawning: true,
+            stop: Arc::new(AtomicBool::new(false)),
+            lit_cells: 0,
+            pending_time: Duration::ZERO,
+            message: HashMap::new(),
+            flash: None,
+            transition: None,
+            runes_created: 0,
+            generators_spawned: 0,
+            steps: 0,
+            start_direction: config.direction,
+            config,
+        };
+        waterfall.reroll_dead_columns();
+        Ok(waterfall)
     }
 
-    pub fn render(&mut self) -> Result<()> {
-        for (y, row) in self.grid.iter().enumerate() {
-            for (x, rune) in row.iter().enumerate() {
-                let new_color = match rune.lifetime {
-                    RUNE_FADE_DURATION.. => rune.color,
-                    0 => (0, 0, 0),
-                    v => (
-                        rune.color.0.saturating_sub(
-                            (rune.color.0 / RUNE_FADE_DURATION) * (RUNE_FADE_DURATION - v),
-                        ),
-                        rune.color.1.saturating_sub(
-                            (rune.color.1 / RUNE_FADE_DURATION) * (RUNE_FADE_DURATION - v),
-                        ),
-                        rune.color.2.saturating_sub(
-                            (rune.color.2 / RUNE_FADE_DURATION) * (RUNE_FADE_DURATION - v),
-                        ),
-                    ),
-                };
+    /// Best effort cleanup of an alphabet, drops characters common terminal
+    /// fonts are known to show as empty boxes or out of line, like dashed
+    /// box drawing lines and the halfwidth katakana sound marks. The font
+    /// in use can't be queried, so glyphs that survive may still be
+    /// missing. `filter_unrenderable` applies it to every alphabet.
+    pub fn filter_renderable(symbols: &str) -> String {
+        symbols.chars().filter(|&c| is_renderable(c)).collect()
+    }
 
-                self.writer
-                    .queue(cursor::MoveTo(x as u16, y as u16))?
-                    .queue(style::PrintStyledContent(
-                        rune.character
-                            .with(Color::Rgb {
-                                r: new_color.0,
-                                g: new_color.1,
-                                b: new_color.2,
-                            }) // .on(Color::Blue)
-                            .attribute(Attribute::Encircled),
-                    ))?
-                    .queue(style::SetForegroundColor(Color::White))?;
-            }
+    /// Settings as they are now, including everything changed through the
+    /// setters since construction. Passing them to `from_config`, or saving
+    /// them with the `serde` feature, brings back the same look.
+    pub fn to_config(&self) -> WaterfallConfig {
+        self.config.clone()
+    }
+
+    /// Switches the alphabet new runes are picked from. Characters without
+    /// width, like combining marks, are rejected or dropped depending on
+    /// `zero_width_symbols`, and only `alphabet_limit` of the rest are
+    /// used. Runes already on screen keep their glyphs unless
+    /// `reroll_on_symbol_change` is set.
+    pub fn set_symbols(&mut self, symbols: &str) -> Result<()> {
+        self.characters = Characters::new(symbols, &self.config)?
+            .limited(self.config.alphabet_limit, &mut self.rng);
+        self.config.symbols = symbols.to_string();
+        if self.config.reroll_on_symbol_change {
+            self.reroll_lit_runes();
         }
-        self.writer.flush()?;
         Ok(())
     }
 
-    pub fn step(&mut self) -> Result<()> {
-        for g in &self.generators {
-            let rune = self.grid.get_rune(g.0, g.1)?;
-            rune.color = self.base_color;
+    /// Gives every column an alphabet of its own, like a gradient of
+    /// character styles from left to right, one string per column. New
+    /// runes of a column are picked from its alphabet instead of
+    /// `symbols`, columns added by `resize` use `symbols`. An empty vector
+    /// goes back to `symbols` everywhere.
+    pub fn set_column_alphabets(&mut self, alphabets: Vec<String>) -> Result<()> {
+        let width = self.grid.width();
+        if alphabets.is_empty() {
+            self.column_alphabets = vec![None; width];
+            return Ok(());
+        }
+        if alphabets.len() != width {
+            return Err(WaterfallError::InvalidConfig(format!(
+                "{} column alphabets for {} columns",
+                alphabets.len(),
+                width
+            )));
         }
 
-        self.generators
-            .retain(|g: &(usize, usize)| self.grid.len() > (g.1 + 1).into());
+        let mut columns = Vec::with_capacity(width);
+        for symbols in &alphabets {
+            let characters = Characters::new(symbols, &self.config)?
+                .limited(self.config.alphabet_limit, &mut self.rng);
+            columns.push(Some(characters));
+        }
+        self.column_alphabets = columns;
+        if self.config.reroll_on_symbol_change {
+            self.reroll_lit_runes();
+        }
+        Ok(())
+    }
 
-        let mut rng = rand::thread_rng();
+    /// Picks a new random subset of `symbols` to use with `alphabet_limit`.
+    pub fn reroll_alphabet(&mut self) {
+        if let Ok(characters) = Characters::new(&self.config.symbols, &self.config) {
+            self.characters = characters.limited(self.config.alphabet_limit, &mut self.rng);
+        }
+    }
 
-        for g in self.generators.iter_mut() {
-            g.1 += 1;
-            let new_rune = self.characters.create_random_rune(self.base_color);
-            self.grid.set_rune(g.0, g.1, new_rune)?;
+    // picks a new glyph from the current alphabet for every lit rune, heads
+    // drawn as `head_glyph` and message glyphs stay as they are
+    fn reroll_lit_runes(&mut self) {
+        let heads: HashSet<(usize, usize)> = match self.config.head_glyph {
+            Some(_) => self
+                .generators
+                .iter()
+                .flat_map(|g| g.columns().map(|x| (x, g.y)))
+                .collect(),
+            None => HashSet::new(),
+        };
+        let width = self.grid.width();
+        for (i, rune) in self.grid.cells_mut().enumerate() {
+            let cell = (i % width, i / width);
+            if rune.character == ' ' || heads.contains(&cell) || self.message.contains_key(&cell) {
+                continue;
+            }
+            rune.character = column_alphabet(&self.column_alphabets, &self.characters, cell.0)
+                .random_char(&mut self.rng);
+        }
+    }
+
+    /// Uses the distinct characters of a UTF-8 file as the alphabet, in the
+    /// order they first appear. Whitespace and newlines are skipped, pass
+    /// them to `set_symbols` to include them.
+    pub fn set_symbols_from_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| WaterfallError::SymbolsFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut seen = HashSet::new();
+        let symbols: String = text
+            .chars()
+            .filter(|c| !c.is_whitespace() && seen.insert(*c))
+            .collect();
+        if symbols.is_empty() {
+            return Err(WaterfallError::InvalidConfig(format!(
+                "symbols file {} has no symbols",
+                path.display()
+            )));
+        }
+        self.set_symbols(&symbols)
+    }
+
+    /// Runes written by generators since construction.
+    pub fn runes_created(&self) -> u64 {
+        self.runes_created
+    }
+
+    /// Generators spawned since construction.
+    pub fn generators_spawned(&self) -> u64 {
+        self.generators_spawned
+    }
+
+    /// Mean luminance of the lit cells in the current frame from 0.0 to 1.0,
+    /// after fading but before any flash or highlight. 0.0 when nothing is
+    /// lit.
+    pub fn average_brightness(&self) -> f32 {
+        let (sum, lit) = self
+            .frame()
+            .iter()
+            .flatten()
+            .filter(|view| view.glyph != ' ')
+            .fold((0.0, 0), |(sum, lit), view| {
+                (sum + color::luminance(view.displayed_color()), lit + 1)
+            });
+        if lit == 0 {
+            0.0
+        } else {
+            sum / lit as f32
+        }
+    }
+
+    /// Share of grid cells currently showing a glyph from 0.0 to 1.0, for
+    /// waiting until the screen has filled up before a transition or text.
+    pub fn coverage(&self) -> f32 {
+        let cells = self.grid.width() * self.grid.height();
+        if cells == 0 {
+            return 0.0;
+        }
+        let lit = self
+            .grid
+            .cells()
+            .filter(|rune| rune.character != ' ')
+            .count();
+        lit as f32 / cells as f32
+    }
+
+    /// Slants the rain of new generators by `degrees` away from straight
+    /// down, positive values lean right. Clamped to 60 degrees either way.
+    pub fn set_angle(&mut self, degrees: f32) {
+        self.config.angle = degrees.clamp(-MAX_RAIN_ANGLE, MAX_RAIN_ANGLE);
+    }
+
+    pub fn color_mode(&self) -> ColorMode {
+        ColorMode::from_colors(self.config.trail_color, self.config.head_color)
+    }
+
+    /// Switches trail and head colors, trails already falling keep theirs.
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        (self.config.trail_color, self.config.head_color) = mode.colors();
+    }
+
+    pub fn density(&self) -> f64 {
+        self.config.density
+    }
+
+    /// Chance of 0.0 to 1.0 per column and step to spawn, from the next
+    /// step on.
+    pub fn set_density(&mut self, density: f64) {
+        self.config.density = density.clamp(0.0, 1.0);
+    }
+
+    pub fn speed(&self) -> (f32, f32) {
+        self.config.speed
+    }
+
+    /// Range of rows per step new generators fall at.
+    pub fn set_speed(&mut self, min: f32, max: f32) {
+        self.config.speed = (min.max(0.0), max.max(min.max(0.0)));
+    }
+
+    /// Gives every new generator the next color of `palette` in turn, so
+    /// the screen shows a mix of fixed colored streams. An empty palette
+    /// goes back to `trail_color`.
+    pub fn set_palette(&mut self, palette: Vec<(u8, u8, u8)>) {
+        self.config.palette = palette;
+        self.palette_cursor = 0;
+    }
+
+    // trail color of the next generator
+    fn next_trail_color(&mut self) -> (u8, u8, u8) {
+        let palette = &self.config.palette;
+        if palette.is_empty() {
+            return self.config.trail_color;
         }
-        for i in 0..self.grid[0].len() {
-            if rng.gen_range(0..GENERATOR_IN_COLUMN.1) <= GENERATOR_IN_COLUMN.0 {
-                self.generators.push((i, 0));
-                let new_rune = self.characters.create_random_rune(self.base_color);
-                self.grid.set_rune(i, 0, new_rune)?;
+        let color = palette[self.palette_cursor % palette.len()];
+        self.palette_cursor = (self.palette_cursor + 1) % palette.len();
+        color
+    }
+
+    /// Makes rain spawn a lot more often in the given columns for a moment,
+    /// for reacting to activity like text the host just printed there. The
+    /// boost wears off by `pulse_decay` every step, columns past the right
+    /// edge are ignored.
+    pub fn pulse_columns(&mut self, columns: &[Range<usize>]) {
+        let width = self.pulse.len();
+        for range in columns {
+            let end = range.end.min(width);
+            if range.start < end {
+                self.pulse[range.start..end].fill(1.0);
             }
         }
+    }
 
-        for row in self.grid.iter_mut() {
-            for rune in row.iter_mut() {
-                if RUNE_LIFETIME.1 + RUNE_FADE_DURATION > rune.lifetime {
-                    if rune.lifetime == 0 {
-                        rune.character = ' ';
-                        continue;
-                    }
+    /// Picks a new random set of `dead_column_fraction` columns that never
+    /// spawn rain.
+    pub fn reroll_dead_columns(&mut self) {
+        let width = self.dead_columns.len();
+        let fraction = self.config.dead_column_fraction.clamp(0.0, 1.0);
+        let count = (width as f32 * fraction).round() as usize;
+
+        self.dead_columns = vec![false; width];
+        for x in index::sample(&mut self.rng, width, count) {
+            self.dead_columns[x] = true;
+        }
+    }
+
+    /// Pauses column `x`, its generators stop falling and its runes stop
+    /// fading until `unfreeze_column`.
+    pub fn freeze_column(&mut self, x: usize) -> Result<()> {
+        self.set_frozen(x, true)
+    }
+
+    pub fn unfreeze_column(&mut self, x: usize) -> Result<()> {
+        self.set_frozen(x, false)
+    }
+
+    fn set_frozen(&mut self, x: usize, frozen: bool) -> Result<()> {
+        let column = self.column_out_of_bounds(x);
+        match self.frozen_columns.get_mut(x) {
+            Some(frozen_column) => *frozen_column = frozen,
+            None => return Err(column),
+        }
+        Ok(())
+    }
+
+    fn column_out_of_bounds(&self, x: usize) -> WaterfallError {
+        WaterfallError::OutOfBounds {
+            x,
+            y: None,
+            width: self.grid.width(),
+            height: self.grid.height(),
+        }
+    }
+
+    /// Sets the depth of every column, 1.0 being the front. Nearer columns
+    /// get longer and brighter trails, values are clamped to 0.1..=1.0.
+    pub fn set_depth_map(&mut self, depth: Vec<f32>) -> Result<()> {
+        if depth.len() != self.depth.len() {
+            return Err(WaterfallError::InvalidConfig(format!(
+                "depth map covers {} columns, grid has {}",
+                depth.len(),
+                self.depth.len()
+            )));
+        }
+        self.depth = depth
+            .into_iter()
+            .map(|d| d.clamp(MIN_COLUMN_DEPTH, 1.0))
+            .collect();
+        Ok(())
+    }
+
+    /// Randomly spreads the columns over `count` evenly spaced depth bands,
+    /// 0 or 1 puts every column at the front.
+    pub fn set_depth_layers(&mut self, count: usize) {
+        let count = count.max(1);
+        for depth in self.depth.iter_mut() {
+            *depth =
+                ((self.rng.gen_range(0..count) + 1) as f32 / count as f32).max(MIN_COLUMN_DEPTH);
+        }
+    }
+
+    /// Marks cells the rain flows behind, generators pass them without
+    /// writing. Replaces the previous mask and blanks the masked cells.
+    pub fn set_mask(&mut self, cells: impl IntoIterator<Item = (usize, usize)>) -> Result<()> {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let mut mask = HashSet::new();
+        for (x, y) in cells {
+            if x >= width || y >= height {
+                return Err(WaterfallError::OutOfBounds {
+                    x,
+                    y: Some(y),
+                    width,
+                    height,
+                });
+            }
+            mask.insert((x, y));
+        }
+
+        for &(x, y) in &mask {
+            let rune = self.grid.get_rune(x, y)?;
+            rune.character = ' ';
+            rune.lifetime = 0;
+        }
+        self.mask = mask;
+        Ok(())
+    }
+
+    pub fn clear_mask(&mut self) {
+        self.mask.clear();
+    }
+
+    /// Paints `text` on top of the rain starting at terminal cell `x`, `y`,
+    /// anything past the right edge is cut off.
+    pub fn set_overlay(
+        &mut self,
+        x: usize,
+        y: usize,
+        text: &str,
+        color: (u8, u8, u8),
+    ) -> Result<()> {
+        let (width, height) = self.output_size();
+        if x >= width || y >= height {
+            return Err(WaterfallError::OutOfBounds {
+                x,
+                y: Some(y),
+                width,
+                height,
+            });
+        }
+
+        self.overlays.push(Overlay {
+            x,
+            y,
+            text: text.chars().take(width - x).collect(),
+            color,
+        });
+        Ok(())
+    }
+
+    pub fn clear_overlays(&mut self) {
+        self.overlays.clear();
+    }
 
-                    rune.lifetime -= 1;
+    /// Writes the non-space characters of `lines` onto the grid from the
+    /// top left corner as fully lit runes that then fade like any other,
+    /// anything outside the grid is cut off.
+    pub fn load_pattern(&mut self, lines: &[&str]) {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        for (y, line) in lines.iter().enumerate().take(height) {
+            for (x, character) in line.chars().enumerate().take(width) {
+                if character == ' ' || self.mask.contains(&(x, y)) {
+                    continue;
                 }
+
+                let rune = self.characters.create_rune(
+                    &mut self.rng,
+                    character,
+                    self.config.trail_color,
+                    self.depth[x],
+                );
+                self.grid[y][x] = rune;
             }
         }
+    }
 
-        for g in &self.generators {
-            let rune = self.grid.get_rune(g.0, g.1)?;
-            rune.color = RUNE_GENERATOR_COLOR;
+    /// Remaps every cell's faded color right before it's drawn, receiving
+    /// the color and the cell's `x`, `y`. Runs once per cell per frame, so
+    /// keep it cheap on big terminals.
+    pub fn set_color_filter(&mut self, filter: Box<ColorFilter>) {
+        self.color_filter = Some(filter);
+    }
+
+    pub fn clear_color_filter(&mut self) {
+        self.color_filter = None;
+    }
+
+    // size in terminal cells the grid is drawn onto
+    fn output_size(&self) -> (usize, usize) {
+        self.output
+    }
+
+    pub fn snapshot(&self) -> WaterfallSnapshot {
+        WaterfallSnapshot {
+            grid: self.grid.clone(),
+            generators: self.generators.clone(),
+        }
+    }
+
+    /// Puts back the state of `snapshot`, which has to come from a grid of
+    /// the same size as this one.
+    pub fn restore(&mut self, snapshot: WaterfallSnapshot) -> Result<()> {
+        let size = (snapshot.grid.width(), snapshot.grid.height());
+        if size != (self.grid.width(), self.grid.height()) {
+            return Err(WaterfallError::InvalidConfig(format!(
+                "snapshot of {}x{} grid doesn't fit {}x{} grid",
+                size.0,
+                size.1,
+                self.grid.width(),
+                self.grid.height()
+            )));
         }
+
+        self.grid = snapshot.grid;
+        self.generators = snapshot.generators;
+        self.lit_cells = self
+            .grid
+            .cells()
+            .filter(|rune| rune.character != ' ')
+            .count();
         Ok(())
     }
+
+    /// Flag that makes `run` return once set, shareable with other threads.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Moves the simulation to a thread of its own that steps `fps` times a
+    /// second and sends every frame over the returned channel, drawing is
+    /// left to the receiver. The thread stops once the `stop_handle` is set
+    /// or the receiver is dropped, and hands the waterfall back.
+    pub fn spawn_simulator(mut self, fps: u32) -> (JoinHandle<Result<Waterfall>>, Receiver<Frame>) {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let frame = Duration::from_secs(1) / fps.max(1);
+            let mut next_frame = Instant::now();
+            while !self.stop.load(Ordering::Relaxed) {
+                self.step()?;
+                let sent = sender.send(Frame {
+                    step: self.steps,
+                    text: self.frame_to_string(),
+                });
+                if sent.is_err() {
+                    break;
+                }
+
+                next_frame += frame;
+                thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+            }
+            self.stop.store(false, Ordering::Relaxed);
+            Ok(self)
+        });
+        (handle, receiver)
+    }
+
+    /// Runs full screen until any key press or mouse movement, following
+    /// terminal resizes, then gives the terminal back as it was.
+    pub fn screensaver(&mut self) -> Result<()> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        stdout
+            .execute(terminal::EnterAlternateScreen)?
+            .execute(event::EnableMouseCapture)?;
+
+        let result = self.screensaver_loop();
+
+        self.renderer.cleanup()?;
+        stdout
+            .execute(event::DisableMouseCapture)?
+            .execute(terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        result
+    }
+
+    fn screensaver_loop(&mut self) -> Result<()> {
+        let frame = self.frame_duration();
+        loop {
+            self.step()?;
+            self.render()?;
+
+            let deadline = Instant::now() + frame;
+            while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+                if !event::poll(timeout)? {
+                    break;
+                }
+                match event::read()? {
+                    Event::Resize(width, height) => self.resize(width as usize, height as usize),
+                    Event::FocusGained | Event::FocusLost => {}
+                    _ => return Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Adapts to a terminal of `width` by `height` cells, keeping whatever
+    /// still fits on screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        debug!("resizing to {}x{}", width, height);
+        self.renderer.invalidate();
+        self.output = (width, height);
+        self.overlays.retain(|o| o.x < width && o.y < height);
+        for overlay in self.overlays.iter_mut() {
+            overlay.text = overlay.text.chars().take(width - overlay.x).collect();
+        }
+        if self.config.virtual_size.is_some() {
+            // the simulation keeps its size, only the sampling changes
+            return;
+        }
+        self.resize_grid(width, self.config.simulated_rows(height));
+    }
+
+    // resizes the simulation to exactly `rows` rows, keeping what still fits
+    pub(crate) fn resize_grid(&mut self, width: usize, rows: usize) {
+        let blank = Rune {
+            character: ' ',
+            lifetime: 0,
+            color: self.config.trail_color,
+        };
+
+        let old_width = self.grid.width();
+        self.grid.resize(width, rows, blank);
+        self.afterglow = resize_cells(&self.afterglow, old_width, width, rows);
+        self.stuck = resize_cells(&self.stuck, old_width, width, rows);
+        self.generators.retain(|g| g.x < width && g.y < rows);
+        for g in self.generators.iter_mut() {
+            g.span = g.span.min(width - g.x);
+        }
+        self.depth.resize(width, 1.0);
+        self.dead_columns.resize(width, false);
+        self.frozen_columns.resize(width, false);
+        self.column_alphabets.resize(width, None);
+        self.wave_heat.resize(width, 0.0);
+        self.pulse.resize(width, 0.0);
+        self.highlights.resize(width, ((0, 0, 0), 0));
+        self.pile.resize(width, 0);
+        for pile in self.pile.iter_mut() {
+            *pile = (*pile).min(rows);
+        }
+        self.mask.retain(|&(x, y)| x < width && y < rows);
+    }
+
+    fn frame_duration(&self) -> Duration {
+        Duration::from_secs(1) / self.config.fps.max(1)
+    }
+
+    /// Animates, stepping and rendering once per frame, until the stop
+    /// handle is set. Then plays `graceful_exit_frames` frames without
+    /// spawning so the remaining trails can fall and fade, and cleans up.
+    pub fn run(&mut self) -> Result<()> {
+        self.run_with_clock(&mut SystemClock)
+    }
+
+    /// Same as `run` but paced by the given time source.
+    pub fn run_with_clock(&mut self, clock: &mut impl Clock) -> Result<()> {
+        let frame = self.frame_duration();
+        let mut last_refresh = clock.now();
+        let mut next_frame = clock.now();
+        let render_interval = self.config.render_interval.max(1);
+        let mut tick = 0;
+
+        while !self.stop.load(Ordering::Relaxed) {
+            if let Some(interval) = self.config.refresh_interval {
+                if clock.now().duration_since(last_refresh) >= interval {
+                    self.clear();
+                    last_refresh = clock.now();
+                }
+            }
+
+            self.step()?;
+            if tick % render_interval == 0 {
+                self.render()?;
+            }
+            tick += 1;
+
+            next_frame += frame;
+            clock.sleep(next_frame.saturating_duration_since(clock.now()));
+        }
+
+        self.spawning = false;
+        for _ in 0..self.config.graceful_exit_frames {
+            self.step()?;
+            self.render()?;
+
+            next_frame += frame;
+            clock.sleep(next_frame.saturating_duration_since(clock.now()));
+        }
+        self.spawning = true;
+        self.stop.store(false, Ordering::Relaxed);
+
+        self.renderer.cleanup()
+    }
+
+    /// Rains down until `text` is uncovered in the middle of the screen,
+    /// holds it there for `hold` while the rain goes on, then lets it fade
+    /// out. Returns once the text is gone, the animation can carry on with
+    /// `run`. Lines of `text` are centered individually.
+    pub fn play_text(&mut self, text: &str, hold: Duration) -> Result<()> {
+        self.play_text_with_clock(text, hold, &mut SystemClock)
+    }
+
+    /// Same as `play_text` but paced by the given time source.
+    pub fn play_text_with_clock(
+        &mut self,
+        text: &str,
+        hold: Duration,
+        clock: &mut impl Clock,
+    ) -> Result<()> {
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let lines: Vec<&str> = text.lines().take(height).collect();
+        let top = (height - lines.len()) / 2;
+        for (i, line) in lines.iter().enumerate() {
+            let glyphs: Vec<char> = line.chars().take(width).collect();
+            let left = (width - glyphs.len()) / 2;
+            for (x, glyph) in glyphs.into_iter().enumerate() {
+                if glyph != ' ' {
+                    self.message.insert((left + x, top + i), (glyph, false));
+                }
+            }
+        }
+
+        let frame = self.frame_duration();
+        let mut next_frame = clock.now();
+
+        // give up on cells the rain misses, like masked or dead columns
+        let mut frames = 0;
+        while self.message.values().any(|&(_, revealed)| !revealed) {
+            if self.stop.load(Ordering::Relaxed) || frames > MESSAGE_REVEAL_FRAMES * height {
+                break;
+            }
+            self.play_frame(clock, &mut next_frame)?;
+            frames += 1;
+        }
+        for (_, revealed) in self.message.values_mut() {
+            *revealed = true;
+        }
+
+        for _ in 0..hold.as_nanos() / frame.as_nanos().max(1) {
+            if self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+            self.play_frame(clock, &mut next_frame)?;
+        }
+
+        for (x, y) in mem::take(&mut self.message).into_keys() {
+            self.grid.get_rune(x, y)?.lifetime = RUNE_FADE_DURATION;
+        }
+        for _ in 0..=RUNE_FADE_DURATION {
+            self.play_frame(clock, &mut next_frame)?;
+        }
+        Ok(())
+    }
+
+    /// Plays the cues of `scene` at their times while the rain goes on and
+    /// returns once the last one is over, the animation can carry on with
+    /// `run`. Bursts and drains put density and spawning back when they
+    /// end. Text holds up the scene, cues due meanwhile start after it.
+    pub fn play_scene(&mut self, scene: Scene) -> Result<()> {
+        self.play_scene_with_clock(scene, &mut SystemClock)
+    }
+
+    /// Same as `play_scene` but paced by the given time source.
+    pub fn play_scene_with_clock(&mut self, scene: Scene, clock: &mut impl Clock) -> Result<()> {
+        let frame = self.frame_duration();
+        let end = scene.duration();
+        let mut cues = scene.cues;
+        cues.sort_by_key(|cue| cue.start);
+        let mut cues = cues.into_iter().peekable();
+        // scene time the burst or drain ends at and the density it restores,
+        // `None` for drains
+        let mut endings: Vec<(Duration, Option<f64>)> = vec![];
+
+        let start = self.steps;
+        let mut next_frame = clock.now();
+        loop {
+            let elapsed = frame * (self.steps - start) as u32;
+            endings.retain(|&(at, density)| {
+                if at > elapsed {
+                    return true;
+                }
+                match density {
+                    Some(density) => self.config.density = density,
+                    None => self.spawning = true,
+                }
+                false
+            });
+            if self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+
+            while let Some(cue) = cues.next_if(|cue| cue.start <= elapsed) {
+                let until = elapsed + cue.duration;
+                match cue.action {
+                    Action::Theme(mode) => self.set_color_mode(mode),
+                    Action::Transition(config) => self.transition_to(*config, cue.duration)?,
+                    Action::Burst(density) => {
+                        endings.push((until, Some(self.config.density)));
+                        self.set_density(density);
+                    }
+                    Action::Text(text) => {
+                        self.play_text_with_clock(&text, cue.duration, clock)?;
+                        next_frame = clock.now();
+                    }
+                    Action::Flash(color) => {
+                        let frames = cue.duration.as_nanos() / frame.as_nanos().max(1);
+                        self.flash(color, frames as usize);
+                    }
+                    Action::Drain => {
+                        endings.push((until, None));
+                        self.spawning = false;
+                    }
+                }
+            }
+            // cues starting right at the end still fire above
+            if elapsed >= end {
+                break;
+            }
+
+            self.play_frame(clock, &mut next_frame)?;
+        }
+
+        for (_, density) in endings.into_iter().rev() {
+            match density {
+                Some(density) => self.config.density = density,
+                None => self.spawning = true,
+            }
+        }
+        Ok(())
+    }
+
+    // steps, renders and sleeps until the frame after `next_frame` is due
+    fn play_frame(&mut self, clock: &mut impl Clock, next_frame: &mut Instant) -> Result<()> {
+        self.step()?;
+        self.render()?;
+        *next_frame += self.frame_duration();
+        clock.sleep(next_frame.saturating_duration_since(clock.now()));
+        Ok(())
+    }
+
+    /// Blanks every cell and drops all generators.
+    pub fn clear(&mut self) {
+        self.generators.clear();
+        self.message.clear();
+        self.pile.fill(0);
+        self.lit_cells = 0;
+        for rune in self.grid.cells_mut() {
+            rune.character = ' ';
+            rune.lifetime = 0;
+        }
+    }
+
+    /// Steps without rendering until trails could have reached every cell,
+    /// so the first frame looks like the animation has been running a while.
+    pub fn warm_up(&mut self) -> Result<()> {
+        let steps = self.grid.height() + (RUNE_LIFETIME.1 + RUNE_FADE_DURATION) as usize;
+        self.step_n(steps)
+    }
+
+    /// Renders one warmed up frame and hands the terminal back, for static
+    /// banners and quick visual checks.
+    pub fn single_frame(&mut self) -> Result<()> {
+        self.warm_up()?;
+        self.render()?;
+        self.renderer.cleanup()
+    }
+
+    pub fn render(&mut self) -> Result<()> {
+        let started = Instant::now();
+        let mut colors = self.frame_colors();
+        if self.config.shimmer_rate > 0.0 && !self.config.reduced_motion {
+            self.shimmer(&mut colors);
+        }
+        if let Some((tint, frames, left)) = self.flash {
+            let strength = FLASH_STRENGTH * left as f32 / frames as f32;
+            if !self.config.reduced_motion {
+                for color in colors.iter_mut().flatten() {
+                    *color = blend(*color, tint, strength);
+                }
+            }
+            self.flash = (left > 1).then_some((tint, frames, left - 1));
+        }
+        for (x, (color, left)) in self.highlights.iter_mut().enumerate() {
+            if *left == 0 {
+                continue;
+            }
+            for (row, cells) in colors.iter_mut().zip(self.grid.rows()) {
+                if cells[x].character != ' ' {
+                    row[x] = blend(row[x], *color, HIGHLIGHT_STRENGTH);
+                }
+            }
+            *left -= 1;
+        }
+        let (tint, strength) = self.config.color_grade;
+        let strength = strength.clamp(0.0, 1.0);
+        if strength > 0.0 {
+            for (row, cells) in colors.iter_mut().zip(self.grid.rows()) {
+                for (color, rune) in row.iter_mut().zip(cells) {
+                    if rune.character != ' ' {
+                        *color = blend(*color, tint, strength);
+                    }
+                }
+            }
+        }
+        let fallback: Vec<char> = self.config.ascii_fallback.chars().collect();
+        let ascii_only = self.config.ascii_only && !fallback.is_empty();
+        let mirror = self.config.mirror_glyphs;
+        let mut glyphs: Vec<Vec<char>> = self
+            .grid
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|rune| match rune.character {
+                        c if mirror => mirrored(c),
+                        c => c,
+                    })
+                    .map(|c| match c {
+                        c if ascii_only && !c.is_ascii() => fallback[c as usize % fallback.len()],
+                        c => c,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        if let Some(connector) = self.config.connector_glyph {
+            self.draw_connectors(connector, &mut glyphs, &mut colors);
+        }
+
+        let width = self.output.0;
+        let rows = self.config.render_mode.grid_rows(self.output.1);
+        if (width, rows) != (self.grid.width(), self.grid.height()) {
+            glyphs = sample(&glyphs, width, rows);
+            colors = sample(&colors, width, rows);
+        }
+
+        self.renderer.begin_frame()?;
+
+        match self.config.render_mode {
+            RenderMode::Glyph => {
+                for (y, row) in glyphs.iter().enumerate() {
+                    for (x, glyph) in row.iter().enumerate() {
+                        self.renderer.draw_cell(x, y, *glyph, colors[y][x])?;
+                    }
+                }
+            }
+            RenderMode::SubCell => {
+                for (y, pair) in colors.chunks(2).enumerate() {
+                    for (x, top) in pair[0].iter().enumerate() {
+                        let bottom = pair.get(1).map_or((0, 0, 0), |row| row[x]);
+                        self.renderer
+                            .draw_cell_on(x, y, SUB_CELL_GLYPH, *top, bottom)?;
+                    }
+                }
+            }
+            RenderMode::Block => {
+                for (y, row) in glyphs.iter().enumerate() {
+                    for (x, glyph) in row.iter().enumerate() {
+                        // blank cells go through `draw_cell` so they are back
+                        // on the terminal's own background
+                        if *glyph == ' ' {
+                            self.renderer.draw_cell(x, y, ' ', colors[y][x])?;
+                        } else {
+                            let color = colors[y][x];
+                            self.renderer.draw_cell_on(x, y, ' ', color, color)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        for overlay in &self.overlays {
+            for (i, glyph) in overlay.text.chars().enumerate() {
+                self.renderer
+                    .draw_cell(overlay.x + i, overlay.y, glyph, overlay.color)?;
+            }
+        }
+        self.renderer.flush()?;
+        trace!("rendered frame in {:?}", started.elapsed());
+        Ok(())
+    }
+
+    // fills the blank gaps between the head and the farthest lit cell of
+    // every trail with a dim `connector`, only streams falling straight
+    // stayed in their column so slanted and sideways ones are skipped
+    fn draw_connectors(
+        &self,
+        connector: char,
+        glyphs: &mut [Vec<char>],
+        colors: &mut [Vec<(u8, u8, u8)>],
+    ) {
+        let intensity = self.config.connector_intensity.clamp(0.0, 1.0);
+        let height = self.grid.height();
+        for g in self
+            .generators
+            .iter()
+            .filter(|g| g.visible() && g.sideways == 0 && g.dx == 0.0)
+        {
+            // rows the trail covers, nearest to the head first
+            let behind: Vec<usize> = match g.heading(self.config.direction) {
+                Direction::Down => (g.y.saturating_sub(g.traveled)..g.y).rev().collect(),
+                Direction::Up => (g.y + 1..(g.y + g.traveled + 1).min(height)).collect(),
+            };
+            let color = scale_color(g.color, intensity);
+            for x in g.columns() {
+                let Some(tail) = behind.iter().rposition(|&y| glyphs[y][x] != ' ') else {
+                    continue;
+                };
+                for &y in &behind[..tail] {
+                    if glyphs[y][x] == ' ' {
+                        glyphs[y][x] = connector;
+                        colors[y][x] = color;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws the lit cells of column `x` in a bright `color` for the next
+    /// `frames` rendered frames. Only affects rendering.
+    pub fn highlight_column(&mut self, x: usize, color: (u8, u8, u8), frames: usize) -> Result<()> {
+        let column = self.column_out_of_bounds(x);
+        match self.highlights.get_mut(x) {
+            Some(highlight) => *highlight = (color, frames),
+            None => return Err(column),
+        }
+        Ok(())
+    }
+
+    /// Tints the whole screen with `color` for the next `frames` rendered
+    /// frames, fading out over them. Only affects rendering.
+    pub fn flash(&mut self, color: (u8, u8, u8), frames: usize) {
+        self.flash = (frames > 0).then_some((color, frames, frames));
+    }
+
+    /// Blends every lit cell toward `tint` by `strength` from 0.0 to 1.0,
+    /// to shift the whole scene warmer or cooler. Applies to the faded
+    /// colors after shimmer, flashes and highlights, 0.0 turns it off. Only
+    /// affects rendering.
+    pub fn set_color_grade(&mut self, tint: (u8, u8, u8), strength: f32) {
+        self.config.color_grade = (tint, strength.clamp(0.0, 1.0));
+    }
+
+    // boosts a fresh random pick of lit cells toward white
+    fn shimmer(&mut self, colors: &mut [Vec<(u8, u8, u8)>]) {
+        let rate = self.config.shimmer_rate.clamp(0.0, 1.0) as f64;
+        for (y, row) in colors.iter_mut().enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                if self.grid[y][x].character == ' ' || !self.render_rng.gen_bool(rate) {
+                    continue;
+                }
+
+                *color = blend(*color, (255, 255, 255), SHIMMER_BOOST);
+            }
+        }
+    }
+
+    /// Current frame as packed RGB bytes, three per cell in row order with
+    /// blank cells black, for LED matrices and framebuffers.
+    pub fn render_rgb_buffer(&self) -> Vec<u8> {
+        self.frame_colors()
+            .into_iter()
+            .flatten()
+            .flat_map(|(r, g, b)| [r, g, b])
+            .collect()
+    }
+
+    /// Current frame as plain text, one line per grid row.
+    pub fn frame_to_string(&self) -> String {
+        self.grid
+            .rows()
+            .map(|row| row.iter().map(|rune| rune.character).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Current frame as a `<pre>` block, every lit glyph wrapped in a span
+    /// colored like it would be in the terminal.
+    pub fn frame_to_html(&self) -> String {
+        let mut html = String::from("<pre>");
+        for (y, row) in self.frame_colors().iter().enumerate() {
+            if y > 0 {
+                html.push('\n');
+            }
+            for (rune, color) in self.grid[y].iter().zip(row) {
+                if rune.character == ' ' {
+                    html.push(' ');
+                    continue;
+                }
+
+                html.push_str(&format!(
+                    "<span style=\"color:#{:02x}{:02x}{:02x}\">",
+                    color.0, color.1, color.2
+                ));
+                match rune.character {
+                    '&' => html.push_str("&amp;"),
+                    '<' => html.push_str("&lt;"),
+                    '>' => html.push_str("&gt;"),
+                    '"' => html.push_str("&quot;"),
+                    '\'' => html.push_str("&#39;"),
+                    c => html.push(c),
+                }
+                html.push_str("</span>");
+            }
+        }
+        html.push_str("</pre>");
+        html
+    }
+
+    /// Every cell in row major order as `(x, y, view)`.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, RuneView)> {
+        self.frame().into_iter().enumerate().flat_map(|(y, row)| {
+            row.into_iter()
+                .enumerate()
+                .map(move |(x, view)| (x, y, view))
+        })
+    }
+
+    fn frame(&self) -> Vec<Vec<RuneView>> {
+        let igniting: HashMap<(usize, usize), f32> = self
+            .generators
+            .iter()
+            .filter(|g| g.ignition > 0 && g.visible())
+            .flat_map(|g| g.columns().map(|x| ((x, g.y), g.ignition_brightness)))
+            .collect();
+        let heads: HashSet<(usize, usize)> = self
+            .generators
+            .iter()
+            .filter(|g| g.visible())
+            .flat_map(|g| g.columns().map(|x| (x, g.y)))
+            .collect();
+        let intensity = self.intensity();
+        let pulse = self.base_pulse();
+        let afterglow = self.config.afterglow_frames.min(u8::MAX as usize) as u8;
+        let width = self.grid.width();
+        let last_row = self.grid.height().saturating_sub(1).max(1) as f32;
+
+        self.grid
+            .rows()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, rune)| {
+                        let brightness = if rune.character == ' ' {
+                            0.0
+                        } else if rune.lifetime == 0 && afterglow > 0 {
+                            let ghost = match self.afterglow.get(y * width + x) {
+                                Some(&ghost) if ghost > 0 => ghost,
+                                _ => afterglow,
+                            };
+                            AFTERGLOW_BRIGHTNESS * self.depth[x] * ghost as f32 / afterglow as f32
+                        } else {
+                            let depth = self.depth[x];
+                            let fade = fade_duration(depth);
+                            let fading = rune.lifetime.min(fade) as f32 / fade as f32;
+                            depth
+                                * fading
+                                * intensity
+                                * igniting.get(&(x, y)).copied().unwrap_or(1.0)
+                        };
+
+                        // heads keep their color so they still stand out
+                        let head = heads.contains(&(x, y));
+                        let color = match self.config.height_gradient {
+                            Some([top, bottom]) if !head => blend(top, bottom, y as f32 / last_row),
+                            _ => rune.color,
+                        };
+                        let color = if head || pulse == 1.0 {
+                            color
+                        } else {
+                            scale_color(color, pulse)
+                        };
+                        RuneView {
+                            glyph: rune.character,
+                            color,
+                            brightness,
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // point on the `intensity_cycle` sine wave the current step is at
+    fn intensity(&self) -> f32 {
+        let Some(cycle) = self.config.intensity_cycle else {
+            return 1.0;
+        };
+        let steps_per_cycle = cycle.as_secs_f64() * self.config.fps.max(1) as f64;
+        if steps_per_cycle <= 0.0 {
+            return 1.0;
+        }
+
+        let phase = self.steps as f64 / steps_per_cycle * std::f64::consts::TAU;
+        let wave = 0.5 + 0.5 * phase.sin() as f32;
+        INTENSITY_RANGE.0 + (INTENSITY_RANGE.1 - INTENSITY_RANGE.0) * wave
+    }
+
+    // factor the `base_pulse` sine wave scales trail colors by this step
+    fn base_pulse(&self) -> f32 {
+        let Some((period, depth)) = self.config.base_pulse else {
+            return 1.0;
+        };
+        let steps_per_period = period.as_secs_f64() * self.config.fps.max(1) as f64;
+        if steps_per_period <= 0.0 {
+            return 1.0;
+        }
+
+        let phase = self.steps as f64 / steps_per_period * std::f64::consts::TAU;
+        let wave = 0.5 - 0.5 * phase.cos() as f32;
+        1.0 - depth.clamp(0.0, 1.0) * wave
+    }
+
+    // faded color of every cell in the grid, blank cells are black
+    fn frame_colors(&self) -> Vec<Vec<(u8, u8, u8)>> {
+        self.frame()
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, view)| {
+                        let color = if self.config.dither && view.brightness > 0.0 {
+                            dither_color(view.color, view.brightness, x, y)
+                        } else {
+                            view.displayed_color()
+                        };
+                        match &self.color_filter {
+                            Some(filter) => filter(color, x, y),
+                            None => color,
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Steps taken so far, see `set_step_index`.
+    pub fn step_index(&self) -> u64 {
+        self.steps
+    }
+
+    /// Brings the simulation to step `n` without rendering, for keeping
+    /// several seeded instances in sync. Instances with the same `seed`,
+    /// size and settings show the same frame at the same step index. Going
+    /// back in time clears the screen and replays from the start.
+    pub fn set_step_index(&mut self, n: u64) -> Result<()> {
+        if n < self.steps {
+            self.clear();
+            self.wave_heat.fill(0.0);
+            self.pulse.fill(0.0);
+            self.afterglow.fill(0);
+            self.stuck.fill(0);
+            self.highlights.fill(((0, 0, 0), 0));
+            self.flash = None;
+            self.transition = None;
+            self.pending_time = Duration::ZERO;
+            self.palette_cursor = 0;
+            self.config.direction = self.start_direction;
+            self.steps = 0;
+        }
+        while self.steps < n {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Crossfades from the current settings to `config` over `over`, counted
+    /// in steps of `fps`. Colors and rates are blended every step, the rest
+    /// like `symbols` switches halfway through. Settings that need a rebuild,
+    /// see `WaterfallConfig::keep_rebuild_settings`, keep their current
+    /// values.
+    pub fn transition_to(&mut self, config: WaterfallConfig, over: Duration) -> Result<()> {
+        Characters::new(&config.symbols, &config)?;
+        let steps = (over.as_secs_f64() * self.config.fps.max(1) as f64).round() as u64;
+        self.transition = Some(Transition {
+            from: self.config.clone(),
+            to: config,
+            steps: steps.max(1),
+            done: 0,
+            switched: false,
+        });
+        Ok(())
+    }
+
+    fn advance_transition(&mut self) -> Result<()> {
+        let Some(mut transition) = self.transition.take() else {
+            return Ok(());
+        };
+        transition.done += 1;
+        let t = (transition.done as f32 / transition.steps as f32).min(1.0);
+        let (from, to) = (&transition.from, &transition.to);
+
+        if !transition.switched && t >= 0.5 {
+            let mut config = to.clone();
+            config.keep_rebuild_settings(&self.config);
+            self.characters = Characters::new(&config.symbols, &config)?
+                .limited(config.alphabet_limit, &mut self.rng);
+            self.config = config;
+            transition.switched = true;
+        }
+
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        self.config.trail_color = blend(from.trail_color, to.trail_color, t);
+        self.config.head_color = blend(from.head_color, to.head_color, t);
+        self.config.density = from.density + (to.density - from.density) * t as f64;
+        self.config.spawn_bias = lerp(from.spawn_bias, to.spawn_bias);
+        self.config.speed = (
+            lerp(from.speed.0, to.speed.0),
+            lerp(from.speed.1, to.speed.1),
+        );
+        self.config.hue_delta = lerp(from.hue_delta, to.hue_delta);
+        self.config.sparkle_rate =
+            from.sparkle_rate + (to.sparkle_rate - from.sparkle_rate) * t as f64;
+        self.config.shimmer_rate = lerp(from.shimmer_rate, to.shimmer_rate);
+
+        if t < 1.0 {
+            self.transition = Some(transition);
+        }
+        Ok(())
+    }
+
+    /// Advances the simulation by `dt` of wall time without rendering, for
+    /// hosts with their own timestep. One step lasts `1 / fps` seconds,
+    /// so a `dt` of a full second at 20 fps runs 20 steps. Time short of a
+    /// whole step is carried over into the next call.
+    pub fn update(&mut self, dt: Duration) -> Result<()> {
+        let frame = self.frame_duration();
+        self.pending_time += dt;
+        while self.pending_time >= frame {
+            self.pending_time -= frame;
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Advances `n` steps without rendering.
+    pub fn step_n(&mut self, n: usize) -> Result<()> {
+        for _ in 0..n {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<()> {
+        if let Some(seed) = self.config.seed {
+            // every step draws from its own stream so instances sharing a
+            // seed agree on a step no matter what they did in between
+            self.rng = StdRng::seed_from_u64(step_seed(seed, self.steps));
+        }
+        self.steps += 1;
+        if self.transition.is_some() {
+            self.advance_transition()?;
+        }
+        if let Some(interval) = self.config.reverse_interval {
+            let steps = (interval.as_secs_f64() * self.config.fps.max(1) as f64).round() as u64;
+            if self.steps.is_multiple_of(steps.max(1)) {
+                self.config.direction = self.config.direction.reversed();
+            }
+        }
+        for g in self.generators.iter().filter(|g| g.visible()) {
+            for x in g.columns() {
+                self.grid.get_rune(x, g.y)?.color = g.color;
+            }
+        }
+
+        let mut generators = mem::take(&mut self.generators);
+        for g in generators.iter_mut() {
+            if g.bounces > 0 && g.visible() && g.sideways == 0 && self.at_end(g) {
+                g.reversed = !g.reversed;
+                g.bounces -= 1;
+            }
+        }
+        self.generators = generators;
+
+        let before = self.generators.len();
+        let height = self.grid.height();
+        let max_travel = self.config.max_trail_travel;
+        for g in self.generators.iter_mut() {
+            // streams running across retire once they leave a side
+            let on_screen = g.sideways != 0
+                || match g.heading(self.config.direction) {
+                    Direction::Down => height > g.y + 1,
+                    Direction::Up => g.y > 0,
+                };
+            if !on_screen || max_travel.is_some_and(|max| g.traveled >= max) {
+                g.retired = true;
+            }
+        }
+        self.drop_retired();
+
+        let mut generators = mem::take(&mut self.generators);
+        for g in generators.iter_mut() {
+            if g.columns().any(|x| self.frozen_columns[x]) {
+                continue;
+            }
+            if g.ignition > 0 {
+                g.ignition -= 1;
+                g.ignition_brightness = self
+                    .rng
+                    .gen_range(IGNITION_BRIGHTNESS.0..IGNITION_BRIGHTNESS.1);
+            }
+
+            let rate = self.config.hesitation_rate.clamp(0.0, 1.0);
+            if rate > 0.0 && self.rng.gen_bool(rate) {
+                continue;
+            }
+
+            g.progress += g.speed;
+            g.speed += g.acceleration;
+            // the end moves with the head, drift can take it over a higher pile
+            while g.progress >= 1.0 && (g.sideways != 0 || !self.at_end(g)) && !g.retired {
+                g.progress -= 1.0;
+                if g.visible() {
+                    self.advance(g)?;
+                    continue;
+                }
+                g.pre_roll -= 1;
+                if g.visible() {
+                    self.write_heads(g)?;
+                }
+            }
+        }
+        self.generators = generators;
+        self.drop_retired();
+
+        if self.config.enable_pileup && self.config.direction == Direction::Down {
+            self.land_generators();
+        }
+        if self.generators.len() < before {
+            trace!("removed {} generators", before - self.generators.len());
+        }
+
+        if self.config.sparkle_rate > 0.0 && !self.config.reduced_motion {
+            self.sparkle()?;
+        }
+
+        if self.spawning
+            && self
+                .config
+                .max_lit_cells
+                .is_none_or(|max| self.lit_cells < max)
+        {
+            self.spawn_generators()?;
+        }
+
+        let mut lit_cells = 0;
+        let width = self.grid.width();
+        let afterglow = self.config.afterglow_frames.min(u8::MAX as usize) as u8;
+        self.afterglow.resize(width * self.grid.height(), 0);
+        self.stuck.resize(width * self.grid.height(), 0);
+        for (i, rune) in self.grid.cells_mut().enumerate() {
+            if self.frozen_columns[i % width] {
+                if rune.character != ' ' {
+                    lit_cells += 1;
+                }
+                continue;
+            }
+            if rune.lifetime > 0 {
+                self.afterglow[i] = 0;
+            }
+            if self.stuck[i] > 0 && rune.lifetime > 0 {
+                self.stuck[i] -= 1;
+                lit_cells += 1;
+                continue;
+            }
+            if RUNE_LIFETIME.1 + RUNE_FADE_DURATION > rune.lifetime {
+                if rune.lifetime == 0 {
+                    // a faded rune lingers as a ghost before blanking
+                    let ghost = &mut self.afterglow[i];
+                    if *ghost == 0 && rune.character != ' ' && afterglow > 0 {
+                        *ghost = afterglow;
+                        lit_cells += 1;
+                    } else if *ghost > 1 {
+                        *ghost -= 1;
+                        lit_cells += 1;
+                    } else {
+                        *ghost = 0;
+                        rune.character = ' ';
+                    }
+                    continue;
+                }
+
+                rune.lifetime -= 1;
+            }
+            if rune.character != ' ' {
+                lit_cells += 1;
+            }
+        }
+        self.lit_cells = lit_cells;
+
+        let animate_heads = self.config.animate_heads
+            && self.config.head_glyph.is_none()
+            && !self.config.reduced_motion;
+        for g in self.generators.iter().filter(|g| g.visible()) {
+            for x in g.columns() {
+                if animate_heads && !self.mask.contains(&(x, g.y)) {
+                    let character = column_alphabet(&self.column_alphabets, &self.characters, x)
+                        .random_char(&mut self.rng);
+                    self.grid.get_rune(x, g.y)?.character = character;
+                }
+                self.grid.get_rune(x, g.y)?.color = self.config.head_color;
+            }
+        }
+
+        // uncovered message glyphs stay put whatever heads passed over them
+        for (&(x, y), &(glyph, revealed)) in &self.message {
+            if revealed {
+                let rune = self.grid.get_rune(x, y)?;
+                rune.character = glyph;
+                rune.lifetime = u8::MAX;
+            }
+        }
+        Ok(())
+    }
+
+    // removes retired generators, their heads take the trail color first so
+    // no cell is left fading out in `head_color`
+    fn drop_retired(&mut self) {
+        for g in self.generators.iter().filter(|g| g.retired && g.visible()) {
+            for x in g.columns() {
+                self.grid[g.y][x].color = g.color;
+            }
+        }
+        self.generators.retain(|g| !g.retired);
+    }
+
+    // row a generator stops at in the direction it's heading
+    fn end_row(&self, g: &Generator) -> usize {
+        match g.heading(self.config.direction) {
+            Direction::Down => g.columns().map(|x| self.bottom_row(x)).min().unwrap_or(0),
+            Direction::Up => 0,
+        }
+    }
+
+    // whether a generator is on or past the row it stops at, a rising pile
+    // can overtake a head
+    fn at_end(&self, g: &Generator) -> bool {
+        let end = self.end_row(g);
+        match g.heading(self.config.direction) {
+            Direction::Down => g.y >= end,
+            Direction::Up => g.y <= end,
+        }
+    }
+
+    // lowest row a generator in column x can reach
+    fn bottom_row(&self, x: usize) -> usize {
+        let bottom = self.grid.height().saturating_sub(1);
+        if self.config.enable_pileup {
+            bottom.saturating_sub(self.config.pileup_floor + self.pile[x])
+        } else {
+            bottom
+        }
+    }
+
+    // generators that reached the top of their column's pile freeze their
+    // head in place and retire
+    fn land_generators(&mut self) {
+        let mut generators = mem::take(&mut self.generators);
+        generators.retain(|g| {
+            // bouncing generators turn around on the pile instead
+            if !g.visible()
+                || g.bounces > 0
+                || g.sideways != 0
+                || g.heading(self.config.direction) != Direction::Down
+                || g.columns().all(|x| g.y < self.bottom_row(x))
+            {
+                return true;
+            }
+
+            for x in g.columns() {
+                let rune = &mut self.grid[g.y][x];
+                if rune.character != ' ' {
+                    rune.lifetime = u8::MAX;
+                    rune.color = g.color;
+                    self.pile[x] = (self.pile[x] + 1).min(self.grid.height());
+                }
+            }
+            false
+        });
+        self.generators = generators;
+    }
+
+    /// Lets every piled up cell fade out and levels the floor again.
+    pub fn reset_pileup(&mut self) {
+        self.pile.fill(0);
+        for rune in self.grid.cells_mut() {
+            if rune.lifetime == u8::MAX {
+                rune.lifetime = RUNE_FADE_DURATION;
+            }
+        }
+    }
+
+    // moves a generator one row down and writes its new head
+    fn advance(&mut self, g: &mut Generator) -> Result<()> {
+        if self.config.head_glyph.is_some() {
+            // the old head joins the trail, which stays random
+            for x in g.columns() {
+                if !self.mask.contains(&(x, g.y)) {
+                    let character = column_alphabet(&self.column_alphabets, &self.characters, x)
+                        .random_char(&mut self.rng);
+                    self.grid.get_rune(x, g.y)?.character = character;
+                }
+            }
+        }
+        // a head about to leave through a side retires where it is, so its
+        // last cell is one the trail owns
+        let mut drift = g.drift + g.dx;
+        let mut x = g.x.checked_add_signed(g.sideways);
+        while drift.abs() >= 1.0 {
+            let step = drift.signum();
+            drift -= step;
+            x = x.and_then(|x| x.checked_add_signed(step as isize));
+        }
+        match x {
+            Some(x) if x + g.span <= self.grid.width() => g.x = x,
+            _ => {
+                g.retired = true;
+                return Ok(());
+            }
+        }
+        g.drift = drift;
+        if g.sideways == 0 {
+            match g.heading(self.config.direction) {
+                Direction::Down => g.y += 1,
+                Direction::Up => g.y -= 1,
+            }
+        }
+        g.traveled += 1;
+        if self.config.hue_delta != 0.0 {
+            let (_, saturation, value) = color::rgb_to_hsv(g.color);
+            g.hue = (g.hue + self.config.hue_delta).rem_euclid(360.0);
+            g.color = color::hsv_to_rgb(g.hue, saturation, value);
+        }
+        self.write_heads(g)
+    }
+
+    // lights up blank cells next to heads for a single fade
+    fn sparkle(&mut self) -> Result<()> {
+        let rate = self.config.sparkle_rate.clamp(0.0, 1.0);
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let heads: Vec<(usize, usize)> = self
+            .generators
+            .iter()
+            .filter(|g| g.visible())
+            .flat_map(|g| g.columns().map(|x| (x, g.y)))
+            .collect();
+
+        for (x, y) in heads {
+            if !self.rng.gen_bool(rate) {
+                continue;
+            }
+
+            let dx = if self.rng.gen_bool(0.5) { 1 } else { -1 };
+            // sparks land beside the head or on the trail just behind it
+            let dy = match self.config.direction {
+                Direction::Down => self.rng.gen_range(-1..=0),
+                Direction::Up => self.rng.gen_range(0..=1),
+            };
+            let (Some(x), Some(y)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+                continue;
+            };
+            if x >= width || y >= height || self.mask.contains(&(x, y)) {
+                continue;
+            }
+
+            let rune = self.grid.get_rune(x, y)?;
+            if rune.character == ' ' {
+                rune.character = column_alphabet(&self.column_alphabets, &self.characters, x)
+                    .random_char(&mut self.rng);
+                rune.color = SPARKLE_COLOR;
+                rune.lifetime = fade_duration(self.depth[x]);
+            }
+        }
+        Ok(())
+    }
+
+    // rows per step for a new generator
+    fn random_speed(&mut self) -> f32 {
+        let (min, max) = self.config.speed;
+        let roll: f32 = self.rng.gen();
+        let roll = match self.config.speed_dist {
+            SpeedDist::Uniform => roll,
+            SpeedDist::Slow => roll.powi(3),
+        };
+        min + (max - min) * roll
+    }
+
+    // way a new stream runs, drawn from `direction_weights`
+    fn random_stream_direction(&mut self) -> StreamDirection {
+        let weights = &self.config.direction_weights;
+        let total: f32 = weights.iter().map(|&(_, weight)| weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return StreamDirection::Straight;
+        }
+
+        let mut roll = self.rng.gen_range(0.0..total);
+        for &(direction, weight) in weights {
+            let weight = weight.max(0.0);
+            if roll < weight {
+                return direction;
+            }
+            roll -= weight;
+        }
+        StreamDirection::Straight
+    }
+
+    fn spawn_generators(&mut self) -> Result<()> {
+        // a grid without rows has nowhere to put a head
+        if self.grid.height() == 0 {
+            return Ok(());
+        }
+
+        let width = self.grid.width();
+        let thickness = self.config.trail_thickness.max(1);
+        let coupling = self.config.wave_coupling > 0.0;
+        if coupling {
+            for heat in self.wave_heat.iter_mut() {
+                *heat *= WAVE_DECAY;
+            }
+        }
+        let mean_heat = self.wave_heat.iter().sum::<f32>() / width.max(1) as f32;
+        let decay = self.config.pulse_decay.clamp(0.0, 1.0);
+        for pulse in self.pulse.iter_mut() {
+            *pulse *= decay;
+        }
+
+        let spacing = self.config.min_column_spacing;
+        // columns whose first row is already driven by a generator, or that
+        // are closer than `min_column_spacing` to a running one
+        let mut reserved = vec![false; width];
+        let reserve = |reserved: &mut [bool], columns: Range<usize>, spacing: usize| {
+            let start = columns.start.saturating_sub(spacing);
+            let end = (columns.end + spacing).min(reserved.len());
+            reserved[start..end].fill(true);
+        };
+        let spawn_row = match self.config.direction {
+            Direction::Down => 0,
+            Direction::Up => self.grid.height() - 1,
+        };
+        for g in &self.generators {
+            if g.y == spawn_row || spacing > 0 {
+                reserve(&mut reserved, g.columns(), spacing);
+            }
+        }
+
+        let mut i = 0;
+        while i < width {
+            let span = thickness.min(width - i);
+            if self.dead_columns[i]
+                || !self.origin_reached(i, width)
+                || reserved[i..i + span].contains(&true)
+                || self.frozen_columns[i..i + span].contains(&true)
+            {
+                i += 1;
+                continue;
+            }
+            let probability = self.spawn_probability(i, width, mean_heat);
+            if !self.rng.gen_bool(probability) {
+                i += 1;
+                continue;
+            }
+
+            let trail_color = self.next_trail_color();
+            let mut generator = Generator::new(i, trail_color);
+            generator.y = spawn_row;
+            generator.span = span;
+            generator.dx = self
+                .config
+                .angle
+                .clamp(-MAX_RAIN_ANGLE, MAX_RAIN_ANGLE)
+                .to_radians()
+                .tan();
+            generator.speed = self.random_speed();
+            generator.acceleration = self.config.gravity;
+            generator.bounces = self.config.bounces;
+            match self.random_stream_direction() {
+                StreamDirection::Straight => {}
+                StreamDirection::Reversed => {
+                    generator.reversed = true;
+                    generator.y = self.grid.height() - 1 - spawn_row;
+                }
+                StreamDirection::DiagonalLeft => generator.dx = -1.0,
+                StreamDirection::DiagonalRight => generator.dx = 1.0,
+                direction @ (StreamDirection::SidewaysLeft | StreamDirection::SidewaysRight) => {
+                    generator.sideways = match direction {
+                        StreamDirection::SidewaysLeft => -1,
+                        _ => 1,
+                    };
+                    generator.dx = 0.0;
+                    generator.y = self.rng.gen_range(0..self.grid.height());
+                }
+            }
+            if self.config.ignition_flicker && !self.config.reduced_motion {
+                generator.ignition = self.rng.gen_range(1..=IGNITION_STEPS);
+                generator.ignition_brightness = self
+                    .rng
+                    .gen_range(IGNITION_BRIGHTNESS.0..IGNITION_BRIGHTNESS.1);
+            }
+            if self.config.spawn_offset_jitter > 0 {
+                generator.pre_roll = self.rng.gen_range(0..=self.config.spawn_offset_jitter);
+            }
+            if generator.visible() {
+                self.write_heads(&generator)?;
+            }
+            reserve(&mut reserved, generator.columns(), spacing);
+            if coupling {
+                self.excite_neighbours(generator.columns());
+            }
+            trace!(
+                "spawned generator at column {} with speed {}",
+                i,
+                generator.speed
+            );
+            self.generators.push(generator);
+            self.generators_spawned += 1;
+            i += span;
+        }
+        Ok(())
+    }
+
+    // whether the opening spread of `spawn_origin` got to column x yet
+    fn origin_reached(&self, x: usize, width: usize) -> bool {
+        let spread_steps = self.config.fps.max(1) as f32;
+        let reach = (self.steps as f32 / spread_steps).min(1.0) * width as f32;
+        let distance = match self.config.spawn_origin {
+            SpawnOrigin::All => return true,
+            SpawnOrigin::Left => x,
+            SpawnOrigin::Center => x.abs_diff(width / 2) * 2,
+            SpawnOrigin::Random => x.abs_diff(self.origin),
+        };
+        distance as f32 <= reach
+    }
+
+    // raises the wave heat of columns around a fresh spawn, falling off
+    // with distance
+    fn excite_neighbours(&mut self, columns: Range<usize>) {
+        let width = self.wave_heat.len();
+        for distance in 1..=WAVE_RADIUS {
+            let heat = 1.0 - distance as f32 / (WAVE_RADIUS + 1) as f32;
+            let left = columns.start.checked_sub(distance);
+            let right = Some(columns.end - 1 + distance).filter(|&x| x < width);
+            for x in left.into_iter().chain(right) {
+                self.wave_heat[x] = self.wave_heat[x].max(heat);
+            }
+        }
+    }
+
+    fn write_heads(&mut self, g: &Generator) -> Result<()> {
+        // faster generators write more rows per fade, their runes fade
+        // sooner so every trail is as long as one of the slowest
+        let lifetime_scale = if self.config.constant_trail_length && g.speed > 0.0 {
+            (self.config.speed.0.max(MIN_TRAIL_SPEED) / g.speed).min(1.0)
+        } else {
+            1.0
+        };
+        for x in g.columns() {
+            self.write_head(x, g.y, g.color, lifetime_scale)?;
+        }
+        Ok(())
+    }
+
+    fn write_head(
+        &mut self,
+        x: usize,
+        y: usize,
+        color: (u8, u8, u8),
+        lifetime_scale: f32,
+    ) -> Result<()> {
+        if self.mask.contains(&(x, y)) {
+            return Ok(());
+        }
+
+        let mut rune = column_alphabet(&self.column_alphabets, &self.characters, x)
+            .create_random_rune(&mut self.rng, color, self.depth[x]);
+        if lifetime_scale < 1.0 {
+            rune.lifetime = ((rune.lifetime as f32 * lifetime_scale).round() as u8).max(1);
+        }
+        if let Some(glyph) = self.config.head_glyph {
+            rune.character = glyph;
+        }
+        let rate = self.config.stuck_glyph_rate.clamp(0.0, 1.0);
+        let stuck = if rate > 0.0 && self.rng.gen_bool(rate) {
+            self.config.stuck_glyph_frames
+        } else {
+            0
+        };
+        if let Some(slot) = self.stuck.get_mut(y * self.grid.width() + x) {
+            *slot = stuck;
+        }
+        if let Some((glyph, revealed)) = self.message.get_mut(&(x, y)) {
+            *revealed = true;
+            rune.character = *glyph;
+            rune.lifetime = u8::MAX;
+        }
+        self.runes_created += 1;
+        self.grid.set_rune(x, y, rune)
+    }
+
+    fn spawn_probability(&self, x: usize, width: usize, mean_heat: f32) -> f64 {
+        let bias = self.config.spawn_bias.clamp(-1.0, 1.0) as f64;
+        let center = width.saturating_sub(1) as f64 / 2.0;
+        let centrality = if center > 0.0 {
+            1.0 - 2.0 * (x as f64 - center).abs() / center
+        } else {
+            1.0
+        };
+
+        let mut density = self.config.density * self.intensity() as f64;
+        if self.config.normalize_density_by_width && width > 0 {
+            density *= DENSITY_REFERENCE_WIDTH as f64 / width as f64;
+        }
+
+        // density moves from quiet columns to ones next to a recent spawn,
+        // so spawns travel sideways in waves while the screen as a whole
+        // keeps spawning at `density`
+        let coupling = self.config.wave_coupling.clamp(0.0, 1.0) as f64;
+        let wave = if mean_heat > 0.0 {
+            1.0 - coupling + coupling * (self.wave_heat[x] / mean_heat) as f64
+        } else {
+            1.0
+        };
+        let pulse = PULSE_DENSITY * self.pulse[x] as f64;
+
+        (density * (1.0 + bias * centrality) * wave + pulse).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded() -> WaterfallConfig {
+        WaterfallConfig {
+            seed: Some(7),
+            ..Default::default()
+        }
+    }
+
+    // rains with `config` for a while, then stops spawning and checks every
+    // step until the screen is empty that the head color only shows where a
+    // generator still is
+    fn assert_drains_without_heads(config: WaterfallConfig) {
+        let head_color = config.head_color;
+        let mut waterfall = Waterfall::headless(config, 30, 15).unwrap();
+        waterfall.set_density(0.3);
+        waterfall.step_n(40).unwrap();
+        waterfall.set_density(0.0);
+
+        for _ in 0..200 {
+            waterfall.step().unwrap();
+            let heads: HashSet<(usize, usize)> = waterfall
+                .generators
+                .iter()
+                .filter(|g| g.visible())
+                .flat_map(|g| g.columns().map(|x| (x, g.y)))
+                .collect();
+            for (x, y, view) in waterfall.cells() {
+                if view.glyph != ' ' && view.color == head_color {
+                    assert!(heads.contains(&(x, y)), "head color left at {}x{}", x, y);
+                }
+            }
+        }
+        assert!(waterfall.generators.is_empty());
+    }
+
+    #[test]
+    fn heads_leaving_the_bottom_take_the_trail_color() {
+        assert_drains_without_heads(seeded());
+    }
+
+    #[test]
+    fn heads_leaving_a_side_take_the_trail_color() {
+        assert_drains_without_heads(WaterfallConfig {
+            angle: 45.0,
+            ..seeded()
+        });
+    }
+
+    #[test]
+    fn heads_capped_by_max_trail_travel_take_the_trail_color() {
+        assert_drains_without_heads(WaterfallConfig {
+            max_trail_travel: Some(5),
+            ..seeded()
+        });
+    }
+
+    #[test]
+    fn piled_heads_take_the_trail_color() {
+        assert_drains_without_heads(WaterfallConfig {
+            enable_pileup: true,
+            ..seeded()
+        });
+    }
+
+    #[test]
+    fn scene_applies_and_undoes_actions_in_turn() {
+        let mut waterfall = Waterfall::headless(seeded(), 20, 10).unwrap();
+        waterfall.set_density(0.0);
+        let ms = Duration::from_millis;
+        let frame = waterfall.frame_duration();
+
+        // a burst spawns and puts the density back once over
+        let scene = Scene::new().at(ms(0), ms(500), Action::Burst(1.0));
+        waterfall
+            .play_scene_with_clock(scene, &mut ManualClock::new())
+            .unwrap();
+        assert!(waterfall.generators_spawned() > 0);
+        assert_eq!(waterfall.density(), 0.0);
+        assert_eq!(
+            waterfall.step_index(),
+            (ms(500).as_nanos() / frame.as_nanos()) as u64
+        );
+
+        // nothing spawns while draining even at full density, then spawning
+        // resumes
+        waterfall.set_density(1.0);
+        let spawned = waterfall.generators_spawned();
+        let scene = Scene::new().at(ms(0), ms(500), Action::Drain).at(
+            ms(100),
+            ms(0),
+            Action::Theme(ColorMode::Amber),
+        );
+        waterfall
+            .play_scene_with_clock(scene, &mut ManualClock::new())
+            .unwrap();
+        assert_eq!(waterfall.generators_spawned(), spawned);
+        assert_eq!(waterfall.color_mode(), ColorMode::Amber);
+        waterfall.step().unwrap();
+        assert!(waterfall.generators_spawned() > spawned);
+    }
+
+    #[test]
+    fn stuck_runes_hold_their_brightness() {
+        let config = WaterfallConfig {
+            density: 0.0,
+            stuck_glyph_rate: 1.0,
+            stuck_glyph_frames: 30,
+            ..seeded()
+        };
+        let mut waterfall = Waterfall::headless(config, 10, 10).unwrap();
+        waterfall.step().unwrap();
+        waterfall.write_head(3, 3, (0, 200, 0), 1.0).unwrap();
+        let lifetime = waterfall.grid[3][3].lifetime;
+
+        waterfall.step_n(30).unwrap();
+        assert_eq!(waterfall.grid[3][3].lifetime, lifetime);
+        waterfall.step().unwrap();
+        assert_eq!(waterfall.grid[3][3].lifetime, lifetime - 1);
+    }
+
+    #[test]
+    fn stuck_runes_follow_the_seed() {
+        let frame = || {
+            let config = WaterfallConfig {
+                density: 0.3,
+                stuck_glyph_rate: 0.2,
+                ..seeded()
+            };
+            let mut waterfall = Waterfall::headless(config, 30, 15).unwrap();
+            waterfall.step_n(50).unwrap();
+            waterfall.frame_to_string()
+        };
+        assert_eq!(frame(), frame());
+    }
+
+    #[test]
+    fn column_alphabets_pick_per_column() {
+        let config = WaterfallConfig {
+            density: 0.5,
+            ..seeded()
+        };
+        let mut waterfall = Waterfall::headless(config, 4, 8).unwrap();
+        assert!(waterfall.set_column_alphabets(vec!["a".into()]).is_err());
+        let alphabets = ["a", "b", "c", "d"].map(String::from).to_vec();
+        waterfall.set_column_alphabets(alphabets).unwrap();
+        waterfall.step_n(20).unwrap();
+
+        for (x, _, view) in waterfall.cells() {
+            if view.glyph != ' ' {
+                assert_eq!(view.glyph, ['a', 'b', 'c', 'd'][x]);
+            }
+        }
+    }
+
+    #[test]
+    fn base_pulse_dips_halfway_through_its_period() {
+        let config = WaterfallConfig {
+            fps: 20,
+            density: 0.0,
+            base_pulse: Some((Duration::from_secs(1), 0.6)),
+            ..seeded()
+        };
+        let mut waterfall = Waterfall::headless(config, 10, 10).unwrap();
+        let rune = Rune {
+            character: 'a',
+            lifetime: u8::MAX,
+            color: (0, 200, 0),
+        };
+        waterfall.grid.set_rune(2, 2, rune).unwrap();
+
+        // green channel of the lit cell at every step of one period
+        let samples: Vec<u8> = (0..=20)
+            .map(|_| {
+                let green = waterfall.frame()[2][2].color.1;
+                waterfall.step().unwrap();
+                green
+            })
+            .collect();
+        assert_eq!(samples[0], 200);
+        assert_eq!(samples[10], 80);
+        assert_eq!(samples[20], 200);
+        assert_eq!(samples.iter().min(), Some(&80));
+    }
+
+    #[test]
+    fn coverage_rises_during_warm_up() {
+        let mut waterfall = Waterfall::headless(seeded(), 30, 15).unwrap();
+        assert_eq!(waterfall.coverage(), 0.0);
+        waterfall.warm_up().unwrap();
+        let coverage = waterfall.coverage();
+        assert!(coverage > 0.0 && coverage <= 1.0);
+    }
+
+    #[test]
+    fn empty_grids_step_and_render() {
+        for (width, height) in [(0, 10), (10, 0), (0, 0)] {
+            let mut waterfall = Waterfall::headless(seeded(), width, height).unwrap();
+            waterfall.step_n(20).unwrap();
+            waterfall.render().unwrap();
+        }
+    }
+
+    #[test]
+    fn resize_to_empty_and_back() {
+        let mut waterfall = Waterfall::headless(seeded(), 20, 10).unwrap();
+        waterfall.step_n(20).unwrap();
+        waterfall.resize(0, 0);
+        waterfall.step_n(5).unwrap();
+        waterfall.render().unwrap();
+        waterfall.resize(20, 10);
+        waterfall.step_n(20).unwrap();
+        waterfall.render().unwrap();
+    }
+
+    #[test]
+    fn scene_fires_cues_at_its_end() {
+        let mut waterfall = Waterfall::headless(seeded(), 20, 10).unwrap();
+        let ms = Duration::from_millis;
+        let scene = Scene::new()
+            .at(ms(0), ms(500), Action::Burst(0.5))
+            .at(ms(200), ms(300), Action::Drain)
+            .at(ms(600), ms(0), Action::Theme(ColorMode::Matrix));
+        waterfall
+            .play_scene_with_clock(scene, &mut ManualClock::new())
+            .unwrap();
+        assert_eq!(waterfall.color_mode(), ColorMode::Matrix);
+    }
+
+    #[test]
+    fn restore_rejects_snapshots_of_another_size() {
+        let mut waterfall = Waterfall::headless(seeded(), 30, 15).unwrap();
+        waterfall.step_n(20).unwrap();
+        let snapshot = waterfall.snapshot();
+
+        for (width, height) in [(20, 10), (40, 20)] {
+            waterfall.resize(width, height);
+            assert!(waterfall.restore(snapshot.clone()).is_err());
+            waterfall.step().unwrap();
+        }
+
+        waterfall.resize(30, 15);
+        waterfall.restore(snapshot).unwrap();
+        waterfall.step_n(20).unwrap();
+    }
+
+    #[test]
+    fn wave_coupling_keeps_the_spawn_rate() {
+        let spawned = |wave_coupling| {
+            let config = WaterfallConfig {
+                wave_coupling,
+                ..seeded()
+            };
+            let mut waterfall = Waterfall::headless(config, 80, 24).unwrap();
+            waterfall.step_n(400).unwrap();
+            waterfall.generators_spawned()
+        };
+        let independent = spawned(0.0);
+        for wave_coupling in [0.5, 1.0] {
+            assert!(spawned(wave_coupling) > independent / 2);
+        }
+    }
+
+    #[test]
+    fn rewinding_matches_a_fresh_instance() {
+        let config = WaterfallConfig {
+            fps: 20,
+            reverse_interval: Some(Duration::from_millis(250)),
+            afterglow_frames: 3,
+            ..seeded()
+        };
+        let mut rewound = Waterfall::headless(config.clone(), 30, 15).unwrap();
+        rewound.set_step_index(17).unwrap();
+        rewound.flash((255, 0, 0), 10);
+        rewound.set_step_index(12).unwrap();
+
+        let mut fresh = Waterfall::headless(config, 30, 15).unwrap();
+        fresh.set_step_index(12).unwrap();
+        assert_eq!(rewound.config.direction, fresh.config.direction);
+        assert_eq!(rewound.frame_to_string(), fresh.frame_to_string());
+    }
+
+    #[test]
+    fn height_gradient_finds_heads_by_position() {
+        let config = WaterfallConfig {
+            density: 0.3,
+            height_gradient: Some([(0, 200, 0), (0, 0, 200)]),
+            ..seeded()
+        };
+        let head_color = config.head_color;
+        let mut waterfall = Waterfall::headless(config, 30, 15).unwrap();
+        // trails in the head color still take the gradient
+        waterfall.set_palette(vec![head_color]);
+        waterfall.step_n(30).unwrap();
+
+        let heads: HashSet<(usize, usize)> = waterfall
+            .generators
+            .iter()
+            .filter(|g| g.visible())
+            .flat_map(|g| g.columns().map(|x| (x, g.y)))
+            .collect();
+        let mut trails = 0;
+        for (x, y, view) in waterfall.cells() {
+            if view.glyph == ' ' || heads.contains(&(x, y)) {
+                continue;
+            }
+            assert_ne!(view.color, head_color, "{}x{} skipped the gradient", x, y);
+            trails += 1;
+        }
+        assert!(trails > 0);
+    }
+
+    #[test]
+    fn resize_keeps_per_cell_counters_in_place() {
+        let mut waterfall = Waterfall::headless(seeded(), 4, 3).unwrap();
+        waterfall.step().unwrap();
+        waterfall.afterglow[4 + 2] = 3;
+        waterfall.stuck[2 * 4 + 3] = 7;
+
+        waterfall.resize(6, 3);
+        assert_eq!(waterfall.afterglow.len(), 18);
+        assert_eq!(waterfall.afterglow[6 + 2], 3);
+        assert_eq!(waterfall.stuck[2 * 6 + 3], 7);
+        assert_eq!(waterfall.afterglow.iter().filter(|&&v| v > 0).count(), 1);
+
+        waterfall.resize(3, 2);
+        assert_eq!(waterfall.afterglow[3 + 2], 3);
+        assert!(waterfall.stuck.iter().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn to_config_keeps_palette_and_color_grade() {
+        let mut waterfall = Waterfall::headless(seeded(), 10, 5).unwrap();
+        waterfall.set_palette(vec![(1, 2, 3), (4, 5, 6)]);
+        waterfall.set_color_grade((200, 100, 0), 0.4);
+
+        let config = waterfall.to_config();
+        assert_eq!(config.palette, vec![(1, 2, 3), (4, 5, 6)]);
+        assert_eq!(config.color_grade, ((200, 100, 0), 0.4));
+    }
+
+    #[test]
+    fn connectors_only_follow_straight_streams() {
+        // connectors drawn over 20 frames at `angle`
+        let connectors = |angle| {
+            let config = WaterfallConfig {
+                angle,
+                density: 0.1,
+                connector_glyph: Some('|'),
+                ..seeded()
+            };
+            let mut waterfall = Waterfall::headless(config, 60, 30).unwrap();
+            waterfall.step_n(20).unwrap();
+            let mut count = 0;
+            for _ in 0..20 {
+                waterfall.step().unwrap();
+                let mut glyphs: Vec<Vec<char>> = waterfall
+                    .grid
+                    .rows()
+                    .map(|row| row.iter().map(|rune| rune.character).collect())
+                    .collect();
+                let mut colors = waterfall.frame_colors();
+                waterfall.draw_connectors('|', &mut glyphs, &mut colors);
+                count += glyphs
+                    .iter()
+                    .flatten()
+                    .filter(|&&glyph| glyph == '|')
+                    .count();
+            }
+            count
+        };
+        assert!(connectors(0.0) > 0);
+        assert_eq!(connectors(45.0), 0);
+    }
+
+    #[test]
+    fn heads_stop_at_a_pile_that_rose_past_them() {
+        let config = |seed, direction_weights| WaterfallConfig {
+            seed: Some(seed),
+            density: 0.3,
+            speed: (3.0, 3.0),
+            enable_pileup: true,
+            direction_weights,
+            ..Default::default()
+        };
+        let mut bouncing = Waterfall::headless(
+            WaterfallConfig {
+                bounces: 2,
+                ..config(0, vec![])
+            },
+            20,
+            10,
+        )
+        .unwrap();
+        bouncing.step_n(300).unwrap();
+
+        let weights = vec![
+            (StreamDirection::Straight, 1.0),
+            (StreamDirection::DiagonalRight, 1.0),
+        ];
+        let mut drifting = Waterfall::headless(config(7, weights), 20, 10).unwrap();
+        drifting.step_n(300).unwrap();
+    }
+
+    #[test]
+    fn players_fit_snapshots_whatever_the_rows() {
+        let mut recorder = Waterfall::headless(seeded(), 20, 9).unwrap();
+        let mut frames = vec![];
+        for _ in 0..5 {
+            recorder.step().unwrap();
+            frames.push(recorder.snapshot());
+        }
+
+        for config in [
+            WaterfallConfig {
+                render_mode: RenderMode::SubCell,
+                ..Default::default()
+            },
+            WaterfallConfig {
+                coarse_factor: 2,
+                ..Default::default()
+            },
+        ] {
+            let config = WaterfallConfig {
+                virtual_size: Some((20, 9)),
+                ..config
+            };
+            let mut player = Player::build(frames.clone(), config, |config| {
+                Waterfall::headless(config, 40, 12)
+            })
+            .unwrap();
+            for index in 0..player.len() {
+                player.seek(index);
+                player.render().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn transitions_keep_rebuild_settings() {
+        let config = WaterfallConfig {
+            render_mode: RenderMode::SubCell,
+            region: Some((2, 1, 16, 8)),
+            differential_render: true,
+            ..seeded()
+        };
+        let mut waterfall = Waterfall::headless(config, 20, 10).unwrap();
+        let rows = waterfall.grid.height();
+        waterfall
+            .transition_to(
+                WaterfallConfig {
+                    coarse_factor: 2,
+                    confined: true,
+                    color_epsilon: 9,
+                    blank_strategy: BlankStrategy::Bulk,
+                    non_terminal_output: NonTerminalOutput::PlainText,
+                    ..WaterfallConfig::film()
+                },
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        waterfall.step_n(100).unwrap();
+
+        let config = waterfall.to_config();
+        assert_eq!(config.symbols, WaterfallConfig::film().symbols);
+        assert_eq!(config.render_mode, RenderMode::SubCell);
+        assert_eq!(config.coarse_factor, 1);
+        assert_eq!(config.region, Some((2, 1, 16, 8)));
+        assert!(!config.confined);
+        assert!(config.differential_render);
+        assert_eq!(
+            config.color_epsilon,
+            WaterfallConfig::default().color_epsilon
+        );
+        assert_eq!(config.blank_strategy, BlankStrategy::default());
+        assert_eq!(config.non_terminal_output, NonTerminalOutput::default());
+
+        waterfall.resize(20, 10);
+        assert_eq!(waterfall.grid.height(), rows);
+    }
+
+    #[test]
+    fn stream_directions_follow_their_weights() {
+        let directions = |direction_weights| {
+            let config = WaterfallConfig {
+                direction_weights,
+                ..seeded()
+            };
+            let mut waterfall = Waterfall::headless(config, 20, 10).unwrap();
+            (0..10_000)
+                .map(|_| waterfall.random_stream_direction())
+                .collect::<Vec<_>>()
+        };
+        let count = |directions: &[StreamDirection], direction| {
+            directions.iter().filter(|&&d| d == direction).count()
+        };
+
+        let weighted = directions(vec![
+            (StreamDirection::Straight, 8.0),
+            (StreamDirection::Reversed, 1.0),
+            (StreamDirection::DiagonalLeft, 1.0),
+        ]);
+        assert!((7_700..8_300).contains(&count(&weighted, StreamDirection::Straight)));
+        assert!((850..1_150).contains(&count(&weighted, StreamDirection::Reversed)));
+        assert!((850..1_150).contains(&count(&weighted, StreamDirection::DiagonalLeft)));
+
+        for weights in [
+            vec![],
+            vec![
+                (StreamDirection::Reversed, 0.0),
+                (StreamDirection::SidewaysLeft, 0.0),
+            ],
+        ] {
+            let directions = directions(weights);
+            assert_eq!(count(&directions, StreamDirection::Straight), 10_000);
+        }
+    }
+
+    #[test]
+    fn run_with_clock_steps_once_per_frame() {
+        // stops the waterfall once a virtual second is over
+        struct StoppingClock {
+            clock: ManualClock,
+            start: Instant,
+            stop: Arc<AtomicBool>,
+        }
+
+        impl Clock for StoppingClock {
+            fn now(&self) -> Instant {
+                self.clock.now()
+            }
+
+            fn sleep(&mut self, duration: Duration) {
+                self.clock.sleep(duration);
+                if self.now().duration_since(self.start) >= Duration::from_secs(1) {
+                    self.stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let config = WaterfallConfig {
+            fps: 20,
+            graceful_exit_frames: 0,
+            ..seeded()
+        };
+        let mut waterfall = Waterfall::headless(config, 20, 10).unwrap();
+        let clock = ManualClock::new();
+        let mut clock = StoppingClock {
+            start: clock.now(),
+            clock,
+            stop: waterfall.stop_handle(),
+        };
+        waterfall.run_with_clock(&mut clock).unwrap();
+        assert_eq!(waterfall.step_index(), 20);
+    }
 }
@@ -1,15 +1,31 @@
-use std::{thread, time::Duration};
+use std::env;
 
-use anyhow::Result;
-use symbol_waterfall::Waterfall;
+use anyhow::{Context, Result};
+use symbol_waterfall::{NonTerminalOutput, Waterfall, WaterfallConfig};
 
 fn main() -> Result<()> {
-    let mut waterfall = Waterfall::new()?;
+    let args: Vec<String> = env::args().collect();
+    let mut config = if Waterfall::prefers_reduced_motion()
+        || args.iter().any(|arg| arg == "--reduced-motion")
+    {
+        WaterfallConfig::reduced_motion()
+    } else {
+        WaterfallConfig::default()
+    };
+    if args.iter().any(|arg| arg == "--plain") {
+        config.non_terminal_output = NonTerminalOutput::PlainText;
+    }
+    let mut waterfall = Waterfall::from_config(config)?;
 
-    loop {
-        waterfall.step()?;
-        waterfall.render()?;
+    if let Some(i) = args.iter().position(|arg| arg == "--symbols-file") {
+        let path = args.get(i + 1).context("--symbols-file needs a path")?;
+        waterfall.set_symbols_from_file(path)?;
+    }
 
-        thread::sleep(Duration::from_millis(50));
+    if args.iter().any(|arg| arg == "--once") {
+        waterfall.single_frame()?;
+        return Ok(());
     }
+    waterfall.run()?;
+    Ok(())
 }
@@ -0,0 +1,135 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Result, Waterfall, WaterfallConfig, WaterfallError, WaterfallSnapshot};
+
+/// Plays back frames captured with `Waterfall::snapshot`, drawn the same
+/// way the live animation is, without simulating anything.
+pub struct Player {
+    frames: Vec<WaterfallSnapshot>,
+    position: usize,
+    paused: Arc<AtomicBool>,
+    waterfall: Waterfall,
+}
+
+impl Player {
+    /// Player drawing to the terminal with the default settings.
+    pub fn from_snapshots(frames: Vec<WaterfallSnapshot>) -> Result<Self> {
+        Self::with_config(frames, WaterfallConfig::default())
+    }
+
+    /// Player drawing to the terminal with `config`, which picks things
+    /// like the render mode and color depth. The frames are scaled onto the
+    /// terminal like with `virtual_size`, so they must all be the same size.
+    pub fn with_config(
+        frames: Vec<WaterfallSnapshot>,
+        mut config: WaterfallConfig,
+    ) -> Result<Self> {
+        let size = frames
+            .first()
+            .map(|frame| (frame.grid.width(), frame.grid.height()));
+        if let Some((width, height)) = size {
+            if let Some(frame) = frames
+                .iter()
+                .find(|frame| (frame.grid.width(), frame.grid.height()) != (width, height))
+            {
+                return Err(WaterfallError::InvalidConfig(format!(
+                    "frames differ in size, {}x{} and {}x{}",
+                    width,
+                    height,
+                    frame.grid.width(),
+                    frame.grid.height()
+                )));
+            }
+        }
+        config.virtual_size = size;
+        Self::build(frames, config, Waterfall::from_config)
+    }
+
+    pub(crate) fn build(
+        frames: Vec<WaterfallSnapshot>,
+        config: WaterfallConfig,
+        waterfall: impl FnOnce(WaterfallConfig) -> Result<Waterfall>,
+    ) -> Result<Self> {
+        let size = config.virtual_size;
+        let mut waterfall = waterfall(config)?;
+        if let Some((width, height)) = size {
+            // virtual_size counts terminal rows, the frames have their grid
+            // rows already, whatever the render mode and coarse_factor
+            waterfall.resize_grid(width, height);
+        }
+
+        Ok(Player {
+            frames,
+            position: 0,
+            paused: Arc::new(AtomicBool::new(false)),
+            waterfall,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Index of the frame shown next.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Jumps to frame `index`, clamped to the last one.
+    pub fn seek(&mut self, index: usize) {
+        self.position = index.min(self.frames.len().saturating_sub(1));
+    }
+
+    /// Flag that holds `play` on the current frame while set, shareable
+    /// with other threads.
+    pub fn pause_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.paused)
+    }
+
+    /// Flag that makes `play` return once set, shareable with other threads.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.waterfall.stop_handle()
+    }
+
+    /// Draws the frame at the current position.
+    pub fn render(&mut self) -> Result<()> {
+        if let Some(frame) = self.frames.get(self.position) {
+            self.waterfall.restore(frame.clone())?;
+            self.waterfall.render()?;
+        }
+        Ok(())
+    }
+
+    /// Shows the frames from the current position on at `fps` frames per
+    /// second until the last one or the stop handle is set, then gives the
+    /// terminal back.
+    pub fn play(&mut self, fps: u32) -> Result<()> {
+        let frame = Duration::from_secs(1) / fps.max(1);
+        let stop = self.waterfall.stop_handle();
+        let mut next_frame = Instant::now();
+
+        while !stop.load(Ordering::Relaxed) && self.position < self.frames.len() {
+            if !self.paused.load(Ordering::Relaxed) {
+                self.render()?;
+                self.position += 1;
+            }
+
+            next_frame += frame;
+            thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+        }
+        stop.store(false, Ordering::Relaxed);
+
+        self.waterfall.renderer.cleanup()
+    }
+}
@@ -0,0 +1,544 @@
+use std::{
+    io::{self, Stdout, Write},
+    panic,
+    sync::Once,
+};
+
+use crate::Result;
+use crossterm::{
+    cursor,
+    style::{self, Attribute, Color, Stylize},
+    terminal::{Clear, ClearType},
+    QueueableCommand,
+};
+
+use crate::color;
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorDepth {
+    /// 24 bit rgb
+    #[default]
+    TrueColor,
+    /// xterm 256 color palette
+    Ansi256,
+    /// the 16 system colors
+    Ansi16,
+    /// no colors, glyphs use the terminal's default foreground
+    Monochrome,
+}
+
+/// Output target for a `Waterfall`. The simulation only ever hands over
+/// fully computed cells, so anything that can place a colored glyph at a
+/// position can display the animation.
+pub trait Renderer {
+    /// Called before the first cell of every frame.
+    fn begin_frame(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn draw_cell(&mut self, x: usize, y: usize, glyph: char, color: (u8, u8, u8)) -> Result<()>;
+
+    /// Same as `draw_cell` but also fills the cell background, targets that
+    /// can't color backgrounds are free to ignore it.
+    fn draw_cell_on(
+        &mut self,
+        x: usize,
+        y: usize,
+        glyph: char,
+        color: (u8, u8, u8),
+        _background: (u8, u8, u8),
+    ) -> Result<()> {
+        self.draw_cell(x, y, glyph, color)
+    }
+
+    fn flush(&mut self) -> Result<()>;
+
+    /// Called when whatever the target remembers of earlier frames is no
+    /// longer on screen, like after a terminal resize, so the next frame is
+    /// drawn in full.
+    fn invalidate(&mut self) {}
+
+    /// Called once the animation is done, gives the target back in a usable
+    /// state.
+    fn cleanup(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// What the crossterm renderer does to the screen before the first frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InitialClear {
+    /// clear the visible screen
+    #[default]
+    All,
+    /// clear the visible screen and the scrollback
+    Purge,
+    /// leave the screen alone and rely on the first frame covering it,
+    /// avoids a black flash when embedding
+    Skip,
+}
+
+/// How the crossterm renderer draws blank cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlankStrategy {
+    /// every blank cell gets its own cursor move and styled space
+    #[default]
+    PerCell,
+    /// runs of blank cells in a row are erased with a single cursor move
+    /// and unstyled spaces
+    Bulk,
+}
+
+/// What `Waterfall::from_config` does when stdout is redirected to a file
+/// or pipe.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NonTerminalOutput {
+    /// fail with an error instead of writing escape codes into the output
+    #[default]
+    Error,
+    /// write every frame as plain text, see `TextRenderer`
+    PlainText,
+}
+
+/// Default renderer, queues crossterm commands into a writer and flushes
+/// them once per frame.
+pub struct CrosstermRenderer<W: Write = Stdout> {
+    writer: W,
+    color_depth: ColorDepth,
+    initial_clear: InitialClear,
+    blank_strategy: BlankStrategy,
+    // start column, row and length of the blanks waiting to be erased
+    blank_run: Option<(usize, usize, usize)>,
+    differential: bool,
+    color_epsilon: u8,
+    // what every cell shows as of the last frame, by row
+    drawn: Vec<Vec<Option<DrawnCell>>>,
+    // column, row, width and height of the screen area cells are drawn in
+    region: Option<(u16, u16, u16, u16)>,
+    confined: bool,
+    started: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct DrawnCell {
+    glyph: char,
+    color: (u8, u8, u8),
+    background: Option<(u8, u8, u8)>,
+}
+
+impl CrosstermRenderer {
+    pub fn new() -> Result<Self> {
+        Self::with_writer(io::stdout())
+    }
+}
+
+impl<W: Write> CrosstermRenderer<W> {
+    pub fn with_writer(writer: W) -> Result<Self> {
+        Ok(CrosstermRenderer {
+            writer,
+            color_depth: ColorDepth::default(),
+            initial_clear: InitialClear::default(),
+            blank_strategy: BlankStrategy::default(),
+            blank_run: None,
+            differential: false,
+            color_epsilon: 0,
+            drawn: vec![],
+            region: None,
+            confined: false,
+            started: false,
+        })
+    }
+
+    pub fn set_initial_clear(&mut self, initial_clear: InitialClear) {
+        self.initial_clear = initial_clear;
+    }
+
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.color_depth = color_depth;
+    }
+
+    pub fn set_blank_strategy(&mut self, blank_strategy: BlankStrategy) {
+        self.blank_strategy = blank_strategy;
+    }
+
+    /// Draws cell 0, 0 at terminal column `x`, row `y` and drops anything
+    /// outside of `width` by `height` cells from there.
+    pub fn set_region(&mut self, x: u16, y: u16, width: u16, height: u16) {
+        self.region = Some((x, y, width, height));
+    }
+
+    /// Leaves the rest of the terminal to the host: every frame puts the
+    /// cursor and colors back the way they were and nothing is cleared
+    /// outside of the cells drawn, whatever `InitialClear` says.
+    pub fn set_confined(&mut self, confined: bool) {
+        self.confined = confined;
+    }
+
+    // moves the cursor to cell x, y, false when it's outside of the region
+    fn move_to(&mut self, x: usize, y: usize) -> Result<bool> {
+        let (x, y) = match self.region {
+            Some((left, top, width, height)) => {
+                if x >= width as usize || y >= height as usize {
+                    return Ok(false);
+                }
+                (left as usize + x, top as usize + y)
+            }
+            None => (x, y),
+        };
+        self.writer.queue(cursor::MoveTo(x as u16, y as u16))?;
+        Ok(true)
+    }
+
+    /// Only draws cells that changed since the last frame, cuts the output
+    /// down a lot since most of the screen stays the same.
+    pub fn set_differential(&mut self, differential: bool) {
+        self.differential = differential;
+        self.drawn.clear();
+    }
+
+    /// With differential drawing, also skips cells whose color moved by no
+    /// more than `epsilon` per channel since they were last drawn.
+    pub fn set_color_epsilon(&mut self, epsilon: u8) {
+        self.color_epsilon = epsilon;
+    }
+
+    // whether the cell already shows about the same, remembers it otherwise
+    fn unchanged(&mut self, x: usize, y: usize, cell: DrawnCell) -> bool {
+        if !self.differential {
+            return false;
+        }
+        if self.drawn.len() <= y {
+            self.drawn.resize(y + 1, vec![]);
+        }
+        let row = &mut self.drawn[y];
+        if row.len() <= x {
+            row.resize(x + 1, None);
+        }
+
+        let epsilon = self.color_epsilon;
+        let close = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            a.0.abs_diff(b.0) <= epsilon
+                && a.1.abs_diff(b.1) <= epsilon
+                && a.2.abs_diff(b.2) <= epsilon
+        };
+        if let Some(drawn) = row[x] {
+            let background = match (drawn.background, cell.background) {
+                (Some(a), Some(b)) => close(a, b),
+                (a, b) => a == b,
+            };
+            // blanks look the same whatever color they were drawn in
+            let color = cell.glyph == ' ' || close(drawn.color, cell.color);
+            if drawn.glyph == cell.glyph && background && color {
+                return true;
+            }
+        }
+        row[x] = Some(cell);
+        false
+    }
+
+    fn erase_blank_run(&mut self) -> Result<()> {
+        if let Some((x, y, mut len)) = self.blank_run.take() {
+            if let Some((_, _, width, _)) = self.region {
+                len = len.min((width as usize).saturating_sub(x));
+            }
+            if self.move_to(x, y)? {
+                self.writer.queue(style::Print(" ".repeat(len)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Closest color the terminal can show for `rgb` at the configured depth.
+    pub fn to_terminal_color(&self, rgb: (u8, u8, u8)) -> Color {
+        match self.color_depth {
+            ColorDepth::TrueColor => Color::Rgb {
+                r: rgb.0,
+                g: rgb.1,
+                b: rgb.2,
+            },
+            ColorDepth::Ansi256 => Color::AnsiValue(color::rgb_to_ansi256(rgb)),
+            ColorDepth::Ansi16 => [
+                Color::Black,
+                Color::DarkRed,
+                Color::DarkGreen,
+                Color::DarkYellow,
+                Color::DarkBlue,
+                Color::DarkMagenta,
+                Color::DarkCyan,
+                Color::Grey,
+                Color::DarkGrey,
+                Color::Red,
+                Color::Green,
+                Color::Yellow,
+                Color::Blue,
+                Color::Magenta,
+                Color::Cyan,
+                Color::White,
+            ][color::rgb_to_ansi16(rgb)],
+            ColorDepth::Monochrome => Color::Reset,
+        }
+    }
+}
+
+impl<W: Write> Renderer for CrosstermRenderer<W> {
+    fn begin_frame(&mut self) -> Result<()> {
+        if self.confined {
+            self.writer
+                .queue(cursor::SavePosition)?
+                .queue(cursor::Hide)?;
+            return Ok(());
+        }
+        if !self.started {
+            self.started = true;
+            self.writer.queue(cursor::Hide)?;
+            match self.initial_clear {
+                InitialClear::All => {
+                    self.writer.queue(Clear(ClearType::All))?;
+                }
+                InitialClear::Purge => {
+                    self.writer.queue(Clear(ClearType::Purge))?;
+                }
+                InitialClear::Skip => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_cell(&mut self, x: usize, y: usize, glyph: char, color: (u8, u8, u8)) -> Result<()> {
+        let cell = DrawnCell {
+            glyph,
+            color,
+            background: None,
+        };
+        if self.unchanged(x, y, cell) {
+            return Ok(());
+        }
+
+        if self.blank_strategy == BlankStrategy::Bulk {
+            if glyph == ' ' {
+                match &mut self.blank_run {
+                    Some((start, row, len)) if *row == y && *start + *len == x => *len += 1,
+                    _ => {
+                        self.erase_blank_run()?;
+                        self.blank_run = Some((x, y, 1));
+                    }
+                }
+                return Ok(());
+            }
+            self.erase_blank_run()?;
+        }
+
+        let color = self.to_terminal_color(color);
+        if !self.move_to(x, y)? {
+            return Ok(());
+        }
+        self.writer
+            .queue(style::PrintStyledContent(
+                glyph
+                    .with(color) // .on(Color::Blue)
+                    .attribute(Attribute::Encircled),
+            ))?
+            .queue(style::SetForegroundColor(Color::White))?;
+        Ok(())
+    }
+
+    fn draw_cell_on(
+        &mut self,
+        x: usize,
+        y: usize,
+        glyph: char,
+        color: (u8, u8, u8),
+        background: (u8, u8, u8),
+    ) -> Result<()> {
+        let cell = DrawnCell {
+            glyph,
+            color,
+            background: Some(background),
+        };
+        if self.unchanged(x, y, cell) {
+            return Ok(());
+        }
+
+        self.erase_blank_run()?;
+        let (color, background) = (
+            self.to_terminal_color(color),
+            self.to_terminal_color(background),
+        );
+        if self.move_to(x, y)? {
+            self.writer
+                .queue(style::PrintStyledContent(glyph.with(color).on(background)))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.erase_blank_run()?;
+        if self.confined {
+            self.writer
+                .queue(style::ResetColor)?
+                .queue(cursor::RestorePosition)?
+                .queue(cursor::Show)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn.clear();
+    }
+
+    fn cleanup(&mut self) -> Result<()> {
+        restore_terminal(&mut self.writer)?;
+        Ok(())
+    }
+}
+
+/// Writes every frame as plain lines of glyphs without colors or cursor
+/// movement, followed by an empty line, for pipes and log files.
+pub struct TextRenderer<W: Write> {
+    writer: W,
+    rows: Vec<Vec<char>>,
+}
+
+impl<W: Write> TextRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        TextRenderer {
+            writer,
+            rows: vec![],
+        }
+    }
+}
+
+impl TextRenderer<Vec<u8>> {
+    // hands out what was written so far and starts over
+    pub(crate) fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.writer)
+    }
+}
+
+impl<W: Write> Renderer for TextRenderer<W> {
+    fn begin_frame(&mut self) -> Result<()> {
+        self.rows.clear();
+        Ok(())
+    }
+
+    fn draw_cell(&mut self, x: usize, y: usize, glyph: char, _: (u8, u8, u8)) -> Result<()> {
+        if self.rows.len() <= y {
+            self.rows.resize(y + 1, vec![]);
+        }
+        let row = &mut self.rows[y];
+        if row.len() <= x {
+            row.resize(x + 1, ' ');
+        }
+        row[x] = glyph;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        for row in &self.rows {
+            let line: String = row.iter().collect();
+            writeln!(self.writer, "{}", line.trim_end())?;
+        }
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// target of headless instances
+pub(crate) struct NullRenderer;
+
+impl Renderer for NullRenderer {
+    fn draw_cell(&mut self, _: usize, _: usize, _: char, _: (u8, u8, u8)) -> Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn restore_terminal(writer: &mut impl Write) -> io::Result<()> {
+    writer
+        .queue(style::ResetColor)?
+        .queue(cursor::Show)?
+        .flush()
+}
+
+/// Chains onto the current panic hook so stdout gets its cursor and colors
+/// back before the panic message is printed. Only the first call installs
+/// the hook, however many waterfalls ask for it.
+pub(crate) fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| chain_panic_hook(io::stdout));
+}
+
+// restores the terminal behind `target` on panic, then runs the previous hook
+fn chain_panic_hook<W: Write>(target: impl Fn() -> W + Send + Sync + 'static) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal(&mut target());
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn terminal_colors_follow_the_color_depth() {
+        let mut renderer = CrosstermRenderer::with_writer(vec![]).unwrap();
+        let red = (255, 0, 0);
+        assert_eq!(
+            renderer.to_terminal_color(red),
+            Color::Rgb { r: 255, g: 0, b: 0 }
+        );
+        renderer.set_color_depth(ColorDepth::Ansi256);
+        assert_eq!(renderer.to_terminal_color(red), Color::AnsiValue(196));
+        renderer.set_color_depth(ColorDepth::Ansi16);
+        assert_eq!(renderer.to_terminal_color(red), Color::Red);
+        assert_eq!(renderer.to_terminal_color((0, 100, 0)), Color::DarkGreen);
+        renderer.set_color_depth(ColorDepth::Monochrome);
+        assert_eq!(renderer.to_terminal_color(red), Color::Reset);
+    }
+
+    #[test]
+    fn panic_hook_restores_the_terminal() {
+        let buffer = SharedBuffer::default();
+        let target = buffer.clone();
+        chain_panic_hook(move || target.clone());
+
+        let result = panic::catch_unwind(|| panic!("boom"));
+        let _ = panic::take_hook();
+
+        assert!(result.is_err());
+        let mut expected = vec![];
+        restore_terminal(&mut expected).unwrap();
+        let written = buffer.0.lock().unwrap();
+        assert!(written
+            .windows(expected.len())
+            .any(|window| window == expected));
+    }
+}
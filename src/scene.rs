@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use crate::{ColorMode, WaterfallConfig};
+
+/// Something a `Scene` does to the waterfall once its cue comes up.
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// switches to other trail and head colors
+    Theme(ColorMode),
+    /// crossfades to other settings over the cue's duration, see
+    /// `Waterfall::transition_to`
+    Transition(Box<WaterfallConfig>),
+    /// spawns rain at this density for the cue's duration, then goes back
+    Burst(f64),
+    /// uncovers the text and holds it for the cue's duration, see
+    /// `Waterfall::play_text`
+    Text(String),
+    /// tints the screen with the color, fading out over the cue's duration
+    Flash((u8, u8, u8)),
+    /// stops spawning for the cue's duration so the screen drains
+    Drain,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Cue {
+    pub(crate) start: Duration,
+    pub(crate) duration: Duration,
+    pub(crate) action: Action,
+}
+
+/// Script of timed actions played with `Waterfall::play_scene`, for intros
+/// and other longer sequences. Times count from the start of the scene in
+/// animation time, so a scene plays out the same whatever the machine.
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub(crate) cues: Vec<Cue>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// Adds `action` starting `start` into the scene and lasting
+    /// `duration`, instant actions like `Action::Theme` ignore it.
+    pub fn at(mut self, start: Duration, duration: Duration, action: Action) -> Self {
+        self.cues.push(Cue {
+            start,
+            duration,
+            action,
+        });
+        self
+    }
+
+    /// Time until the last cue is over.
+    pub fn duration(&self) -> Duration {
+        self.cues
+            .iter()
+            .map(|cue| cue.start + cue.duration)
+            .max()
+            .unwrap_or_default()
+    }
+}
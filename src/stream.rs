@@ -0,0 +1,58 @@
+use std::io::Write;
+
+use crate::Result;
+
+use crate::{Renderer, TextRenderer};
+
+/// Sends frames over a socket or any other writer as plain text, each one
+/// prefixed with its length in bytes as a big endian `u32`. Pass it to
+/// `Waterfall::with_renderer_and_size` with a `TcpStream` and the remote
+/// display's size to drive it, see the `frame_server` example.
+pub struct FrameStreamWriter<W: Write> {
+    writer: W,
+    frame: TextRenderer<Vec<u8>>,
+    frame_interval: usize,
+    frames: usize,
+}
+
+impl<W: Write> FrameStreamWriter<W> {
+    pub fn new(writer: W) -> Self {
+        FrameStreamWriter {
+            writer,
+            frame: TextRenderer::new(vec![]),
+            frame_interval: 1,
+            frames: 0,
+        }
+    }
+
+    /// Only sends every `interval`th frame, for slow links.
+    pub fn set_frame_interval(&mut self, interval: usize) {
+        self.frame_interval = interval.max(1);
+    }
+}
+
+impl<W: Write> Renderer for FrameStreamWriter<W> {
+    fn begin_frame(&mut self) -> Result<()> {
+        self.frame.begin_frame()
+    }
+
+    fn draw_cell(&mut self, x: usize, y: usize, glyph: char, color: (u8, u8, u8)) -> Result<()> {
+        self.frame.draw_cell(x, y, glyph, color)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.frame.flush()?;
+        let payload = self.frame.take_output();
+        let frame = self.frames;
+        self.frames += 1;
+        if !frame.is_multiple_of(self.frame_interval) {
+            return Ok(());
+        }
+
+        self.writer
+            .write_all(&(payload.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&payload)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}